@@ -0,0 +1,706 @@
+// generates random programs from the grammar the parser accepts, restricted so that both interpreters
+// have to agree on the result: every program terminates, every variable keeps the type it started with
+// and type errors only show up where they were put on purpose.
+//
+// Termination comes from three rules: loops count to a small constant with a counter nothing else
+// assigns, functions and classes can only use the ones declared before them so nothing recurses,
+// and every call is charged what its body costs so nested calls in loops can't blow up
+
+use std::mem;
+
+use crate::rng::Rng;
+
+/// the most calls a single body, or the whole program, is allowed to make
+const MAX_COST: usize = 2000;
+const MAX_FUNCTIONS: usize = 5;
+const MAX_CLASSES: usize = 3;
+/// statements in the whole program, nested ones included
+const MAX_STATEMENTS: usize = 60;
+const MAX_EXPRESSION_DEPTH: usize = 3;
+const MAX_BLOCK_DEPTH: usize = 3;
+
+/// one source line, or something followed by a braced body
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Line(String),
+    /// `header { body }`, then `else { body }` if there is one. An empty header is a plain block
+    Block {
+        header:    String,
+        body:      Vec<Stmt>,
+        else_body: Option<Vec<Stmt>>,
+    },
+}
+
+/// kept as a tree rather than text so the shrinker can take statements out and still have a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+}
+
+impl Program {
+
+    pub fn render(&self) -> String {
+        let mut source = String::new();
+        render(&self.statements, 0, &mut source);
+        source
+    }
+}
+
+fn render(statements: &[Stmt], depth: usize, source: &mut String) {
+    let indent = "  ".repeat(depth);
+    for statement in statements {
+        source.push_str(&indent);
+        match statement {
+            Stmt::Line(line) => source.push_str(line),
+            Stmt::Block { header, body, else_body } => {
+                if !header.is_empty() {
+                    source.push_str(header);
+                    source.push(' ');
+                }
+                source.push_str("{\n");
+                render(body, depth + 1, source);
+                source.push_str(&indent);
+                source.push('}');
+                if let Some(else_body) = else_body {
+                    source.push_str(" else {\n");
+                    render(else_body, depth + 1, source);
+                    source.push_str(&indent);
+                    source.push('}');
+                }
+            },
+        }
+        source.push('\n');
+    }
+}
+
+/// keeps a binary expression together when it's the operand of a unary one, `-(a + b)` rather than `-a + b`
+fn parenthesize(operand: String) -> String {
+    if operand.contains(' ') {format!("({})", operand)} else {operand}
+}
+
+/// program for the seed, the same seed always gives the same program
+pub fn generate(seed: u64) -> Program {
+    let mut generator = Generator {
+        rng:       Rng::new(seed),
+        scopes:    vec![vec![]],
+        classes:   vec![],
+        names:     0,
+        functions: 0,
+        budget:    MAX_STATEMENTS,
+        context:   Context::default(),
+    };
+    let count = generator.rng.between(6, 14);
+    Program {statements: generator.statements(count, 0)}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Number,
+    String,
+    Bool,
+}
+
+const TYPES: [Type; 3] = [Type::Number, Type::String, Type::Bool];
+
+#[derive(Debug, Clone)]
+struct Signature {
+    params:  Vec<Type>,
+    returns: Type,
+    /// calls made by one call of this, itself included
+    cost:    usize,
+}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    /// can be assigned, but only ever with the same type
+    Variable(Type),
+    /// loop counters, never assigned by anything but the loop
+    Constant(Type),
+    /// holds an instance of the class at this index, never reassigned
+    Instance(usize),
+    /// the second signature is the closure it returns, if it returns one
+    Function(Signature, Option<Signature>),
+    Class(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    name:       String,
+    superclass: Option<usize>,
+    init:       Signature,
+    fields:     Vec<(String, Type)>,
+    methods:    Vec<(String, Signature)>,
+}
+
+/// what the statement being generated is inside of
+#[derive(Debug, Clone, Copy)]
+struct Context {
+    /// inside a function or method returning this
+    returns:    Option<Type>,
+    /// inside a method of the class at this index, so `this` and maybe `super` can be used
+    class:      Option<usize>,
+    /// how many times the current statement runs per call of the body, from the loops around it
+    multiplier: usize,
+    /// calls made so far by the body
+    cost:       usize,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context {returns: None, class: None, multiplier: 1, cost: 0}
+    }
+}
+
+/// a call the expression generator could make, the arguments are filled in once it's picked
+enum Part {
+    Text(String),
+    Args(Vec<Type>),
+}
+
+struct Generator {
+    rng:       Rng,
+    scopes:    Vec<Vec<(String, Binding)>>,
+    classes:   Vec<Class>,
+    /// numbers every name so no two declarations ever clash or shadow each other
+    names:     usize,
+    functions: usize,
+    budget:    usize,
+    context:   Context,
+}
+
+impl Generator {
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.names += 1;
+        format!("{}{}", prefix, self.names)
+    }
+
+    fn declare(&mut self, name: String, binding: Binding) {
+        self.scopes.last_mut().expect("Generator: no scope").push((name, binding));
+    }
+
+    fn visible(&self) -> Vec<(String, Binding)> {
+        self.scopes.iter().flatten().cloned().collect()
+    }
+
+    fn affordable(&self, cost: usize) -> bool {
+        self.context.cost + cost * self.context.multiplier <= MAX_COST
+    }
+
+    fn charge(&mut self, cost: usize) {
+        self.context.cost += cost * self.context.multiplier;
+    }
+
+    fn statements(&mut self, count: usize, depth: usize) -> Vec<Stmt> {
+        let mut statements = vec![];
+        for _ in 0..count {
+            if self.budget == 0 {break};
+            self.budget -= 1;
+            statements.extend(self.statement(depth));
+        }
+        statements
+    }
+
+    /// a braced body in its own scope
+    fn block(&mut self, depth: usize) -> Vec<Stmt> {
+        self.scopes.push(vec![]);
+        let count = self.rng.between(1, 3);
+        let body = self.statements(count, depth + 1);
+        self.scopes.pop();
+        body
+    }
+
+    fn statement(&mut self, depth: usize) -> Vec<Stmt> {
+        let nested = depth < MAX_BLOCK_DEPTH;
+        let in_body = self.context.returns.is_some() || self.context.class.is_some();
+
+        match self.rng.below(100) {
+            0..=19 => self.var_declaration(),
+            20..=24 => self.instance_declaration().unwrap_or_else(|| self.print()),
+            25..=34 => self.assignment().unwrap_or_else(|| self.print()),
+            35..=44 if nested => self.if_statement(depth),
+            45..=51 if nested => self.for_statement(depth),
+            52..=55 if nested => self.while_statement(depth),
+            56..=58 if nested => vec![Stmt::Block {header: String::new(), body: self.block(depth), else_body: None}],
+            59..=66 => self.call_statement().unwrap_or_else(|| self.print()),
+            67..=76 if !in_body && self.functions < MAX_FUNCTIONS => self.fun_declaration(depth),
+            77..=82 if !in_body && depth == 0 && self.classes.len() < MAX_CLASSES => self.class_declaration(),
+            83..=86 if self.context.returns.is_some() => self.early_return(),
+            87..=91 if self.context.class.is_some() => self.field_assignment(),
+            _ => self.print(),
+        }
+    }
+
+    fn print(&mut self) -> Vec<Stmt> {
+        let value = self.any(0);
+        vec![Stmt::Line(format!("print {};", value))]
+    }
+
+    fn var_declaration(&mut self) -> Vec<Stmt> {
+        let ty = *self.rng.pick(&TYPES);
+        // the initializer comes first so it can't see the variable it's initialising
+        let value = self.expr(ty, 0);
+        let name = self.fresh("v");
+        self.declare(name.clone(), Binding::Variable(ty));
+        vec![Stmt::Line(format!("var {} = {};", name, value))]
+    }
+
+    fn instance_declaration(&mut self) -> Option<Vec<Stmt>> {
+        let construct = self.construct(0)?;
+        let (value, class) = construct;
+        let name = self.fresh("v");
+        self.declare(name.clone(), Binding::Instance(class));
+        Some(vec![Stmt::Line(format!("var {} = {};", name, value))])
+    }
+
+    fn assignment(&mut self) -> Option<Vec<Stmt>> {
+        let mut targets = vec![];
+        for (name, binding) in self.visible() {
+            match binding {
+                Binding::Variable(ty) => targets.push((name, ty)),
+                Binding::Instance(class) => for (field, ty) in &self.classes[class].fields {
+                    targets.push((format!("{}.{}", name, field), *ty));
+                },
+                _ => {},
+            }
+        }
+        // a string assigned in a loop can double every time round, so strings are only ever initialised
+        targets.retain(|(_, ty)| *ty != Type::String);
+        if targets.is_empty() {return None};
+
+        let (target, ty) = self.rng.pick(&targets).clone();
+        let value = self.expr(ty, 0);
+        Some(vec![Stmt::Line(format!("{} = {};", target, value))])
+    }
+
+    fn field_assignment(&mut self) -> Vec<Stmt> {
+        let class = self.context.class.expect("Generator: field assignment outside a class");
+        let mut fields = self.classes[class].fields.clone();
+        fields.retain(|(_, ty)| *ty != Type::String);
+        if fields.is_empty() {return self.print()};
+
+        let (field, ty) = self.rng.pick(&fields).clone();
+        let value = self.expr(ty, 0);
+        vec![Stmt::Line(format!("this.{} = {};", field, value))]
+    }
+
+    fn if_statement(&mut self, depth: usize) -> Vec<Stmt> {
+        let condition = self.any(0);
+        let body = self.block(depth);
+        let else_body = if self.rng.chance(40) {Some(self.block(depth))} else {None};
+        vec![Stmt::Block {header: format!("if ({})", condition), body, else_body}]
+    }
+
+    /// runs the body a little more than times, so the multiplier is charged before the body is made
+    fn looping(&mut self, times: usize, body: impl FnOnce(&mut Self) -> Vec<Stmt>) -> Vec<Stmt> {
+        let multiplier = self.context.multiplier;
+        self.context.multiplier *= times + 1;
+        let body = body(self);
+        self.context.multiplier = multiplier;
+        body
+    }
+
+    fn for_statement(&mut self, depth: usize) -> Vec<Stmt> {
+        let times = self.rng.between(0, 3);
+        let counter = self.fresh("i");
+
+        self.scopes.push(vec![(counter.clone(), Binding::Constant(Type::Number))]);
+        let body = self.looping(times, |generator| generator.block(depth));
+        self.scopes.pop();
+
+        let header = format!("for (var {0} = 0; {0} < {1}; {0} = {0} + 1)", counter, times);
+        vec![Stmt::Block {header, body, else_body: None}]
+    }
+
+    /// counts in the condition, so no statement in the body can be the one that keeps it going
+    fn while_statement(&mut self, depth: usize) -> Vec<Stmt> {
+        let times = self.rng.between(0, 3);
+        let counter = self.fresh("w");
+        self.declare(counter.clone(), Binding::Constant(Type::Number));
+
+        let body = self.looping(times, |generator| generator.block(depth));
+        vec![
+            Stmt::Line(format!("var {} = 0;", counter)),
+            Stmt::Block {header: format!("while (({0} = {0} + 1) <= {1})", counter, times), body, else_body: None},
+        ]
+    }
+
+    fn call_statement(&mut self) -> Option<Vec<Stmt>> {
+        let ty = *self.rng.pick(&TYPES);
+        let call = self.call(ty, 0)?;
+        Some(vec![Stmt::Line(format!("{};", call))])
+    }
+
+    fn early_return(&mut self) -> Vec<Stmt> {
+        let returns = self.context.returns.expect("Generator: return outside a function");
+        let condition = self.any(0);
+        let value = self.expr(returns, 0);
+        vec![Stmt::Block {header: format!("if ({})", condition), body: vec![Stmt::Line(format!("return {};", value))], else_body: None}]
+    }
+
+    fn signature(&mut self) -> Signature {
+        let arity = self.rng.between(0, 3);
+        Signature {
+            params:  (0..arity).map(|_| *self.rng.pick(&TYPES)).collect(),
+            returns: *self.rng.pick(&TYPES),
+            cost:    1,
+        }
+    }
+
+    fn fun_declaration(&mut self, depth: usize) -> Vec<Stmt> {
+        self.functions += 1;
+        let name = self.fresh("f");
+        let mut signature = self.signature();
+        let (params, body, closure, cost) = self.function(&signature, self.context.class, depth, true);
+        signature.cost = cost;
+
+        self.declare(name.clone(), Binding::Function(signature, closure));
+        vec![Stmt::Block {header: format!("fun {}({})", name, params), body, else_body: None}]
+    }
+
+    /// the parameter list and body of a function or method, which may end by returning a closure
+    /// when `closure` allows it, along with the closure's signature and what a call costs
+    fn function(&mut self, signature: &Signature, class: Option<usize>, depth: usize, closure: bool)
+        -> (String, Vec<Stmt>, Option<Signature>, usize) {
+        self.scopes.push(vec![]);
+        let params: Vec<String> = signature.params.iter().map(|ty| {
+            let name = self.fresh("p");
+            self.declare(name.clone(), Binding::Variable(*ty));
+            name
+        }).collect();
+
+        let outer = mem::replace(&mut self.context, Context {
+            returns: Some(signature.returns),
+            class,
+            ..Context::default()
+        });
+
+        let count = self.rng.between(1, 4);
+        let mut body = self.statements(count, depth + 1);
+        let mut returned = None;
+        if closure && self.rng.chance(30) {
+            let name = self.fresh("f");
+            let mut inner = self.signature();
+            let (inner_params, inner_body, _, cost) = self.function(&inner, class, depth + 1, false);
+            inner.cost = cost;
+            body.push(Stmt::Block {header: format!("fun {}({})", name, inner_params), body: inner_body, else_body: None});
+            body.push(Stmt::Line(format!("return {};", name)));
+            returned = Some(inner);
+        } else {
+            let value = self.expr(signature.returns, 0);
+            body.push(Stmt::Line(format!("return {};", value)));
+        }
+
+        let cost = self.context.cost + 1;
+        self.context = outer;
+        self.scopes.pop();
+        (params.join(", "), body, returned, cost)
+    }
+
+    fn class_declaration(&mut self) -> Vec<Stmt> {
+        let name = self.fresh("C");
+        let index = self.classes.len();
+        let superclass = if !self.classes.is_empty() && self.rng.chance(50) {
+            Some(self.rng.below(self.classes.len()))
+        } else {
+            None
+        };
+
+        // a subclass starts out with everything it inherits
+        let class = match superclass {
+            Some(superclass) => Class {name: name.clone(), superclass: Some(superclass), ..self.classes[superclass].clone()},
+            None => Class {
+                name:       name.clone(),
+                superclass: None,
+                init:       Signature {params: vec![], returns: Type::Bool, cost: 1},
+                fields:     vec![],
+                methods:    vec![],
+            },
+        };
+        self.classes.push(class);
+
+        let mut body = vec![];
+        if superclass.is_none() || self.rng.chance(50) {
+            body.push(self.initializer(index));
+        }
+        for _ in 0..self.rng.between(0, 3) {
+            body.push(self.method(index));
+        }
+
+        self.declare(name.clone(), Binding::Class(index));
+        let header = match superclass {
+            Some(superclass) => format!("class {} < {}", name, self.classes[superclass].name),
+            None => format!("class {}", name),
+        };
+        vec![Stmt::Block {header, body, else_body: None}]
+    }
+
+    /// stores some of its parameters and a few other values in fields, after calling the superclass's init
+    fn initializer(&mut self, class: usize) -> Stmt {
+        let arity = self.rng.between(0, 3);
+        let types: Vec<Type> = (0..arity).map(|_| *self.rng.pick(&TYPES)).collect();
+
+        self.scopes.push(vec![]);
+        let params: Vec<(String, Type)> = types.iter().map(|ty| {
+            let name = self.fresh("p");
+            self.declare(name.clone(), Binding::Variable(*ty));
+            (name, *ty)
+        }).collect();
+        let outer = mem::replace(&mut self.context, Context {class: Some(class), ..Context::default()});
+
+        let mut body = vec![];
+        if let Some(superclass) = self.classes[class].superclass {
+            let init = self.classes[superclass].init.clone();
+            let args = self.args(&init.params, 0);
+            self.charge(init.cost);
+            body.push(Stmt::Line(format!("super.init({});", args)));
+        }
+        for (param, ty) in params.iter() {
+            if self.rng.chance(70) {
+                let field = self.fresh("x");
+                body.push(Stmt::Line(format!("this.{} = {};", field, param)));
+                self.classes[class].fields.push((field, *ty));
+            }
+        }
+        for _ in 0..self.rng.between(0, 2) {
+            let ty = *self.rng.pick(&TYPES);
+            let value = self.expr(ty, 0);
+            let field = self.fresh("x");
+            body.push(Stmt::Line(format!("this.{} = {};", field, value)));
+            self.classes[class].fields.push((field, ty));
+        }
+
+        let cost = self.context.cost + 1;
+        self.context = outer;
+        self.scopes.pop();
+
+        self.classes[class].init = Signature {params: types, returns: Type::Bool, cost};
+        let params: Vec<String> = params.into_iter().map(|(name, _)| name).collect();
+        Stmt::Block {header: format!("init({})", params.join(", ")), body, else_body: None}
+    }
+
+    /// either overrides an inherited method with the same signature, or adds a new one
+    fn method(&mut self, class: usize) -> Stmt {
+        let inherited = self.classes[class].methods.clone();
+        let (name, mut signature) = if !inherited.is_empty() && self.rng.chance(40) {
+            self.rng.pick(&inherited).clone()
+        } else {
+            (self.fresh("m"), self.signature())
+        };
+
+        let (params, body, _, cost) = self.function(&signature, Some(class), 1, false);
+        signature.cost = cost;
+
+        let methods = &mut self.classes[class].methods;
+        match methods.iter_mut().find(|(method, _)| *method == name) {
+            Some(method) => method.1 = signature,
+            None => methods.push((name.clone(), signature)),
+        }
+        Stmt::Block {header: format!("{}({})", name, params), body, else_body: None}
+    }
+
+    fn args(&mut self, params: &[Type], depth: usize) -> String {
+        let args: Vec<String> = params.iter().map(|ty| self.expr(*ty, depth + 1)).collect();
+        args.join(", ")
+    }
+
+    /// `C(args)` for some class in scope, along with the class
+    fn construct(&mut self, depth: usize) -> Option<(String, usize)> {
+        let classes: Vec<(String, usize)> = self.visible().into_iter()
+            .filter_map(|(name, binding)| match binding {
+                Binding::Class(class) => Some((name, class)),
+                _ => None,
+            })
+            .filter(|(_, class)| self.affordable(self.classes[*class].init.cost))
+            .collect();
+        if classes.is_empty() {return None};
+
+        let (name, class) = self.rng.pick(&classes).clone();
+        let init = self.classes[class].init.clone();
+        self.charge(init.cost);
+        let args = self.args(&init.params, depth);
+        Some((format!("{}({})", name, args), class))
+    }
+
+    /// a call to anything in scope that returns the type
+    fn call(&mut self, ty: Type, depth: usize) -> Option<String> {
+        let mut candidates: Vec<(Vec<Part>, usize)> = vec![];
+
+        for (name, binding) in self.visible() {
+            match binding {
+                Binding::Function(signature, None) if signature.returns == ty => {
+                    candidates.push((vec![Part::Text(name), Part::Args(signature.params)], signature.cost));
+                },
+                Binding::Function(signature, Some(closure)) if closure.returns == ty => {
+                    let cost = signature.cost + closure.cost;
+                    candidates.push((vec![Part::Text(name), Part::Args(signature.params), Part::Args(closure.params)], cost));
+                },
+                Binding::Instance(class) => for (method, signature) in &self.classes[class].methods {
+                    if signature.returns != ty {continue};
+                    let parts = vec![Part::Text(format!("{}.{}", name, method)), Part::Args(signature.params.clone())];
+                    candidates.push((parts, signature.cost));
+                },
+                Binding::Class(class) => {
+                    let init = &self.classes[class].init;
+                    for (method, signature) in &self.classes[class].methods {
+                        if signature.returns != ty {continue};
+                        let parts = vec![
+                            Part::Text(name.clone()), Part::Args(init.params.clone()),
+                            Part::Text(format!(".{}", method)), Part::Args(signature.params.clone()),
+                        ];
+                        candidates.push((parts, init.cost + signature.cost));
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        // only calls up the hierarchy, calling a method through `this` could land in an override that calls back
+        if let Some(superclass) = self.context.class.and_then(|class| self.classes[class].superclass) {
+            for (method, signature) in &self.classes[superclass].methods {
+                if signature.returns != ty {continue};
+                let parts = vec![Part::Text(format!("super.{}", method)), Part::Args(signature.params.clone())];
+                candidates.push((parts, signature.cost));
+            }
+        }
+
+        candidates.retain(|(_, cost)| self.affordable(*cost));
+        if candidates.is_empty() {return None};
+
+        let (parts, cost) = candidates.swap_remove(self.rng.below(candidates.len()));
+        self.charge(cost);
+        let mut call = String::new();
+        for part in parts {
+            match part {
+                Part::Text(text) => call.push_str(&text),
+                Part::Args(params) => {
+                    let args = self.args(&params, depth);
+                    call.push_str(&format!("({})", args));
+                },
+            }
+        }
+        Some(call)
+    }
+
+    fn expr(&mut self, ty: Type, depth: usize) -> String {
+        if depth >= MAX_EXPRESSION_DEPTH || self.rng.chance(35) {
+            return self.leaf(ty);
+        }
+        match ty {
+            Type::Number => match self.rng.below(7) {
+                0 => {
+                    let operand = self.expr(Type::Number, depth + 1);
+                    format!("-{}", parenthesize(operand))
+                },
+                1 => format!("({})", self.expr(Type::Number, depth + 1)),
+                2..=4 => {
+                    let operator = *self.rng.pick(&["+", "-", "*", "/"]);
+                    format!("{} {} {}", self.expr(Type::Number, depth + 1), operator, self.expr(Type::Number, depth + 1))
+                },
+                _ => self.call(ty, depth).unwrap_or_else(|| self.leaf(ty)),
+            },
+            Type::String => match self.rng.below(4) {
+                0 | 1 => format!("{} + {}", self.expr(Type::String, depth + 1), self.expr(Type::String, depth + 1)),
+                2 => format!("({})", self.expr(Type::String, depth + 1)),
+                _ => self.call(ty, depth).unwrap_or_else(|| self.leaf(ty)),
+            },
+            Type::Bool => match self.rng.below(8) {
+                0 | 1 => {
+                    let operator = *self.rng.pick(&["<", "<=", ">", ">="]);
+                    format!("{} {} {}", self.expr(Type::Number, depth + 1), operator, self.expr(Type::Number, depth + 1))
+                },
+                2 => {
+                    let operator = *self.rng.pick(&["==", "!="]);
+                    format!("{} {} {}", self.any(depth + 1), operator, self.any(depth + 1))
+                },
+                3 => {
+                    let operand = self.any(depth + 1);
+                    format!("!{}", parenthesize(operand))
+                },
+                4 | 5 => {
+                    let operator = *self.rng.pick(&["and", "or"]);
+                    format!("{} {} {}", self.expr(Type::Bool, depth + 1), operator, self.expr(Type::Bool, depth + 1))
+                },
+                6 => format!("({})", self.expr(Type::Bool, depth + 1)),
+                _ => self.call(ty, depth).unwrap_or_else(|| self.leaf(ty)),
+            },
+        }
+    }
+
+    /// a value of whatever type, including the ones nothing is typed as, and now and then a type error
+    fn any(&mut self, depth: usize) -> String {
+        match self.rng.below(100) {
+            0..=59 => {
+                let ty = *self.rng.pick(&TYPES);
+                self.expr(ty, depth)
+            },
+            60..=64 => "nil".to_string(),
+            65..=69 => {
+                let operator = *self.rng.pick(&["and", "or"]);
+                format!("{} {} {}", self.any(depth + 1), operator, self.any(depth + 1))
+            },
+            70..=79 => {
+                let values: Vec<String> = self.visible().into_iter()
+                    .filter(|(_, binding)| matches!(binding, Binding::Function(..) | Binding::Class(_) | Binding::Instance(_)))
+                    .map(|(name, _)| name)
+                    .collect();
+                if values.is_empty() {return self.leaf(Type::Number)};
+                self.rng.pick(&values).clone()
+            },
+            80..=84 if self.context.class.is_some() => "this".to_string(),
+            85..=89 => match self.construct(depth) {
+                Some((value, _)) => value,
+                None => self.leaf(Type::String),
+            },
+            90 => self.type_error(depth),
+            _ => self.leaf(Type::Bool),
+        }
+    }
+
+    /// something both interpreters have to report as the same runtime error
+    fn type_error(&mut self, depth: usize) -> String {
+        let number = self.expr(Type::Number, depth + 1);
+        let string = self.expr(Type::String, depth + 1);
+        match self.rng.below(6) {
+            0 => format!("{} + {}", number, string),
+            1 => format!("-{}", parenthesize(string)),
+            2 => format!("{} < {}", string, number),
+            3 => format!("{}()", *self.rng.pick(&["1", "\"f\"", "nil", "true"])),
+            4 => format!("{}.x", number),
+            _ => format!("{} * {}", string, string),
+        }
+    }
+
+    fn leaf(&mut self, ty: Type) -> String {
+        let mut readable = vec![];
+        for (name, binding) in self.visible() {
+            match binding {
+                Binding::Variable(of) | Binding::Constant(of) if of == ty => readable.push(name),
+                Binding::Instance(class) => for (field, of) in &self.classes[class].fields {
+                    if *of == ty {readable.push(format!("{}.{}", name, field))};
+                },
+                _ => {},
+            }
+        }
+        if let Some(class) = self.context.class {
+            for (field, of) in &self.classes[class].fields {
+                if *of == ty {readable.push(format!("this.{}", field))};
+            }
+        }
+
+        if !readable.is_empty() && self.rng.chance(50) {
+            return self.rng.pick(&readable).clone();
+        }
+        match ty {
+            Type::Number => match self.rng.below(20) {
+                0 | 1 => format!("{}.{}", self.rng.below(10), self.rng.between(1, 9)),
+                2 | 3 => format!("{}", self.rng.between(10, 100)),
+                // past what fits in 32 bits, and in 64
+                4 => self.rng.pick(&["4294967295", "4294967296", "18446744073709551616"]).to_string(),
+                _ => format!("{}", self.rng.below(10)),
+            },
+            Type::String => self.rng.pick(&["\"a\"", "\"lox\"", "\"\"", "\"b c\"", "\"1\"", "\"two\nlines\""]).to_string(),
+            Type::Bool => self.rng.pick(&["true", "false"]).to_string(),
+        }
+    }
+}
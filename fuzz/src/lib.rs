@@ -0,0 +1,33 @@
+pub mod generator;
+pub mod rng;
+pub mod runner;
+pub mod shrink;
+
+use crate::runner::Outcome;
+
+/// the source with the annotations testing/conformance.rs checks, describing what `expected` did
+pub fn annotate(source: &str, expected: &Outcome) -> String {
+    let mut lines: Vec<String> = source.lines().map(String::from).collect();
+    let mut errors = expected.stderr.lines().filter(|line| !line.is_empty());
+
+    match expected.exit_code {
+        70 => {
+            let message = errors.next().unwrap_or_default();
+            let line = errors.next()
+                .and_then(|trace| trace.strip_prefix("[line "))
+                .and_then(|trace| trace.split(']').next())
+                .and_then(|line| line.parse::<usize>().ok())
+                .unwrap_or(1);
+            if let Some(text) = lines.get_mut(line - 1) {
+                text.push_str(&format!(" // expect runtime error: {}", message));
+            }
+        },
+        65 => lines.extend(errors.map(|error| format!("// {}", error))),
+        _ => {},
+    }
+    lines.extend(expected.stdout.lines().map(|line| format!("// expect: {}", line)));
+
+    let mut annotated = lines.join("\n");
+    annotated.push('\n');
+    annotated
+}
@@ -0,0 +1,77 @@
+use std::env;
+use std::fs;
+use std::panic;
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fuzzlox::generator::generate;
+use fuzzlox::runner::compare;
+use fuzzlox::shrink::shrink;
+use fuzzlox::annotate;
+
+const USAGE: &str = "Usage: fuzzlox [--seed=<n>] [--iterations=<n>] [--save] [--print]";
+
+fn main() {
+    let mut seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0);
+    let mut iterations = 1000;
+    let mut save = false;
+    let mut print = false;
+
+    for arg in env::args().skip(1) {
+        let parsed = match arg.as_str() {
+            "--save" => {
+                save = true;
+                Ok(())
+            },
+            "--print" => {
+                print = true;
+                Ok(())
+            },
+            arg if arg.starts_with("--seed=") => arg["--seed=".len()..].parse().map(|n| seed = n),
+            arg if arg.starts_with("--iterations=") => arg["--iterations=".len()..].parse().map(|n| iterations = n),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(64);
+            },
+        };
+        if parsed.is_err() {
+            eprintln!("{}", USAGE);
+            process::exit(64);
+        }
+    }
+
+    // interpreter panics are caught and reported as divergences, the default hook would only add noise
+    panic::set_hook(Box::new(|_| {}));
+
+    println!("fuzzing seeds {} to {}", seed, seed + iterations - 1);
+    let mut failures = 0;
+    for seed in seed..seed + iterations {
+        let program = generate(seed);
+        if print {println!("--- seed {}\n{}", seed, program.render())};
+        let Some(divergence) = compare(&program.render()) else {continue};
+        failures += 1;
+
+        let kind = divergence.kind();
+        let smallest = shrink(&program, |candidate| {
+            compare(&candidate.render()).is_some_and(|divergence| divergence.kind() == kind)
+        });
+        let source = smallest.render();
+        let divergence = compare(&source).expect("the shrunk program always diverges");
+
+        println!("--- seed {}\n{}", seed, source);
+        println!("rulox   (exit {}):\n{}{}", divergence.rulox.exit_code, divergence.rulox.stdout, divergence.rulox.stderr);
+        println!("unrulox (exit {}):\n{}{}", divergence.unrulox.exit_code, divergence.unrulox.stdout, divergence.unrulox.stderr);
+
+        if save {
+            // annotated with what rulox did, check that's the right answer before keeping the file
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("testing").join("fuzz").join(format!("seed_{}.rlx", seed));
+            fs::create_dir_all(path.parent().unwrap()).expect("can't create testing/fuzz");
+            fs::write(&path, annotate(&source, &divergence.rulox)).expect("can't save the reproducer");
+            println!("saved {}", path.display());
+        }
+    }
+
+    println!("{} of {} programs diverged", failures, iterations);
+    if failures > 0 {process::exit(1)};
+}
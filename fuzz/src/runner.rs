@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use rulox::diagnostics::ErrorFormat;
+use rulox::lox::Lox;
+use unrulox::vm::{InterpretResult, VM};
+
+/// what a run left behind, the exit code is the one the binary would have exited with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub stdout:    String,
+    pub stderr:    String,
+    pub exit_code: i32,
+}
+
+impl Outcome {
+
+    /// the parts both interpreters have to agree on. A runtime error is its message and the line in the
+    /// innermost frame of the trace, the rest of the trace is allowed to differ. Compile errors are only
+    /// the first one, since the two resynchronise after an error in different places
+    fn observable(&self) -> (Vec<&str>, i32, Option<&str>, Option<&str>) {
+        let mut errors = self.stderr.lines().filter(|line| !line.is_empty());
+        let message = errors.next();
+        let line = match self.exit_code {
+            70 => errors.next().and_then(|trace| trace.split(']').next()),
+            _ => None,
+        };
+        (self.stdout.lines().collect(), self.exit_code, message, line)
+    }
+}
+
+/// somewhere to capture output that both interpreters can write into
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// a panic is a crash either way, it comes out as exit code 101 like an uncaught one would
+fn catch(stdout: &Capture, stderr: &Capture, run: impl FnOnce() -> i32) -> Outcome {
+    let exit_code = match panic::catch_unwind(AssertUnwindSafe(run)) {
+        Ok(exit_code) => exit_code,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            writeln!(stderr.clone(), "panicked: {}", message).unwrap();
+            101
+        },
+    };
+    Outcome {stdout: stdout.contents(), stderr: stderr.contents(), exit_code}
+}
+
+pub fn run_rulox(source: &str) -> Outcome {
+    let (stdout, stderr) = (Capture::default(), Capture::default());
+    let lox = Lox::with_output(Arc::new(Mutex::new(stdout.clone())), Arc::new(Mutex::new(stderr.clone())));
+    // unrulox only has the book's format, so that's what gets compared
+    lox.set_error_format(ErrorFormat::Classic);
+    catch(&stdout, &stderr, || lox.run_source(source))
+}
+
+pub fn run_unrulox(source: &str) -> Outcome {
+    let (stdout, stderr) = (Capture::default(), Capture::default());
+    catch(&stdout, &stderr, || {
+        let mut vm = VM::with_output(Box::new(stdout.clone()), Box::new(stderr.clone()));
+        match vm.interpret(source) {
+            InterpretResult::Ok => 0,
+            InterpretResult::CompileError => 65,
+            InterpretResult::RuntimeError => 70,
+        }
+    })
+}
+
+/// a program the two interpreters disagree on, with what each of them did
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub rulox:   Outcome,
+    pub unrulox: Outcome,
+}
+
+impl Divergence {
+
+    /// what kind of disagreement it is, the shrinker keeps to programs with the same kind
+    pub fn kind(&self) -> (i32, i32) {
+        (self.rulox.exit_code, self.unrulox.exit_code)
+    }
+}
+
+/// runs the source through both, None when they agree
+pub fn compare(source: &str) -> Option<Divergence> {
+    let rulox = run_rulox(source);
+    let unrulox = run_unrulox(source);
+    if rulox.observable() == unrulox.observable() {
+        None
+    } else {
+        Some(Divergence {rulox, unrulox})
+    }
+}
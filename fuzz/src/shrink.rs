@@ -0,0 +1,52 @@
+use crate::generator::{Program, Stmt};
+
+/// greedily makes the program smaller for as long as `diverges` still holds: statements are dropped,
+/// else branches removed and blocks replaced by their bodies, until no single step keeps it failing
+pub fn shrink(program: &Program, diverges: impl Fn(&Program) -> bool) -> Program {
+    let mut smallest = program.clone();
+    'shrinking: loop {
+        for statements in candidates(&smallest.statements) {
+            let candidate = Program {statements};
+            if diverges(&candidate) {
+                smallest = candidate;
+                continue 'shrinking;
+            }
+        }
+        return smallest;
+    }
+}
+
+/// every list one step smaller than `statements`, biggest steps first
+fn candidates(statements: &[Stmt]) -> Vec<Vec<Stmt>> {
+    let mut candidates = vec![];
+
+    for index in 0..statements.len() {
+        let mut without = statements.to_vec();
+        without.remove(index);
+        candidates.push(without);
+    }
+
+    for (index, statement) in statements.iter().enumerate() {
+        let Stmt::Block { header, body, else_body } = statement else {continue};
+        let replace = |with: Vec<Stmt>| {
+            let mut replaced = statements[..index].to_vec();
+            replaced.extend(with);
+            replaced.extend_from_slice(&statements[index + 1..]);
+            replaced
+        };
+
+        candidates.push(replace(body.clone()));
+        if let Some(else_body) = else_body {
+            candidates.push(replace(else_body.clone()));
+            candidates.push(replace(vec![Stmt::Block {header: header.clone(), body: body.clone(), else_body: None}]));
+            for smaller in self::candidates(else_body) {
+                let else_body = Some(smaller);
+                candidates.push(replace(vec![Stmt::Block {header: header.clone(), body: body.clone(), else_body}]));
+            }
+        }
+        for smaller in self::candidates(body) {
+            candidates.push(replace(vec![Stmt::Block {header: header.clone(), body: smaller, else_body: else_body.clone()}]));
+        }
+    }
+    candidates
+}
@@ -0,0 +1,19 @@
+use fuzzlox::generator::generate;
+use fuzzlox::runner::compare;
+
+// a fixed slice of seeds so the two interpreters stay in step, `cargo run -p fuzzlox` goes looking further
+#[test]
+fn seeds() {
+    let failures: Vec<String> = (0..500)
+        .filter_map(|seed| {
+            let source = generate(seed).render();
+            compare(&source).map(|divergence| {
+                format!("--- seed {}\n{}\nrulox: {:?}\nunrulox: {:?}", seed, source, divergence.rulox, divergence.unrulox)
+            })
+        })
+        .collect();
+
+    if !failures.is_empty() {
+        panic!("{} programs diverged\n\n{}", failures.len(), failures.join("\n\n"));
+    }
+}
@@ -1,3 +1,13 @@
+// AST constructors hand back the wrapping `Expr` variant rather than the node itself
+#![allow(clippy::new_ret_no_self)]
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::callable::LoxCallable;
+use crate::class::{LoxClass, LoxInstance};
 use crate::lox::Token;
 
 #[derive(Debug, Clone)]
@@ -6,47 +16,115 @@ pub enum Value {
     Float(f64),
     Integer(u32),
     Boolean(bool),
+    Callable(Arc<dyn LoxCallable>),
+    Class(Arc<LoxClass>),
+    Instance(Arc<Mutex<LoxInstance>>),
     Null,
 }
 
-impl ToString for Value {
-    fn to_string(&self) -> String {
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Value::String(s) => s.clone(),
-            Value::Float(f) => f.to_string(),
-            Value::Integer(i) => i.to_string(),
-            Value::Boolean(b) => b.to_string(),
-            Value::Null => "null".to_string(),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Callable(c) => write!(f, "{}", c),
+            Value::Class(c) => write!(f, "{}", c),
+            Value::Instance(i) => write!(f, "{}", i.lock().unwrap()),
+            Value::Null => write!(f, "nil"),
         }
     }
 }
 
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// every node the resolver binds gets a unique id, so the interpreter can look its depth up later
+fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
 pub trait Visitor<T> {
+    fn visit_assign(&self, assign: &Assign) -> T;
     fn visit_binary(&self, binary: &Binary) -> T;
+    fn visit_call(&self, call: &Call) -> T;
+    fn visit_get(&self, get: &Get) -> T;
     fn visit_grouping(&self, grouping: &Grouping) -> T;
     fn visit_literal(&self, literal: &Literal) -> T;
+    fn visit_logical(&self, logical: &Logical) -> T;
+    fn visit_set(&self, set: &Set) -> T;
+    fn visit_super(&self, super_expr: &Super) -> T;
+    fn visit_this(&self, this: &This) -> T;
     fn visit_unary(&self, unary: &Unary) -> T;
+    fn visit_variable(&self, variable: &Variable) -> T;
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
+    Assign(Assign),
     Binary(Binary),
+    Call(Call),
+    Get(Get),
     Grouping(Grouping),
     Literal(Literal),
+    Logical(Logical),
+    Set(Set),
+    Super(Super),
+    This(This),
     Unary(Unary),
+    Variable(Variable),
 }
 
 impl Expr {
     pub fn accept<T>(&self, visitor: &dyn Visitor<T>) -> T {
         match self {
+            Expr::Assign(assign) => visitor.visit_assign(assign),
             Expr::Binary(binary) => visitor.visit_binary(binary),
+            Expr::Call(call) => visitor.visit_call(call),
+            Expr::Get(get) => visitor.visit_get(get),
             Expr::Grouping(grouping) => visitor.visit_grouping(grouping),
             Expr::Literal(literal) => visitor.visit_literal(literal),
+            Expr::Logical(logical) => visitor.visit_logical(logical),
+            Expr::Set(set) => visitor.visit_set(set),
+            Expr::Super(super_expr) => visitor.visit_super(super_expr),
+            Expr::This(this) => visitor.visit_this(this),
             Expr::Unary(unary) => visitor.visit_unary(unary),
+            Expr::Variable(variable) => visitor.visit_variable(variable),
+        }
+    }
+
+    /// the bytes of source the expression was parsed from, from its first token to its last
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Expr::Assign(assign) => assign.name.span.start..assign.value.span().end,
+            Expr::Binary(binary) => binary.left.span().start..binary.right.span().end,
+            Expr::Call(call) => call.callee.span().start..call.paren.span.end,
+            Expr::Get(get) => get.object.span().start..get.name.span.end,
+            Expr::Grouping(grouping) => grouping.span.clone(),
+            Expr::Literal(literal) => literal.span.clone(),
+            Expr::Logical(logical) => logical.left.span().start..logical.right.span().end,
+            Expr::Set(set) => set.object.span().start..set.value.span().end,
+            Expr::Super(super_expr) => super_expr.keyword.span.start..super_expr.method.span.end,
+            Expr::This(this) => this.keyword.span.clone(),
+            Expr::Unary(unary) => unary.operator.span.start..unary.right.span().end,
+            Expr::Variable(variable) => variable.name.span.clone(),
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Assign {
+    pub id: usize,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+impl Assign {
+    pub fn new(name: Token, value: Box<Expr>) -> Expr {
+        Expr::Assign(Assign { id: next_id(), name, value })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Binary {
     pub left: Box<Expr>,
@@ -60,25 +138,105 @@ impl Binary {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub callee: Box<Expr>,
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+
+impl Call {
+    pub fn new(callee: Box<Expr>, paren: Token, arguments: Vec<Expr>) -> Expr {
+        Expr::Call(Call { callee, paren, arguments })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
+impl Get {
+    pub fn new(object: Box<Expr>, name: Token) -> Expr {
+        Expr::Get(Get { object, name })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Grouping {
     pub expression: Box<Expr>,
+    /// from the '(' to the ')', neither of which is kept as a token
+    pub span: Range<usize>,
 }
 
 impl Grouping {
-    pub fn new(expression: Box<Expr>) -> Expr {
-        Expr::Grouping(Grouping { expression })
+    pub fn new(expression: Box<Expr>, span: Range<usize>) -> Expr {
+        Expr::Grouping(Grouping { expression, span })
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Literal {
     pub value: Value,
+    /// the token it came from, or an empty span where the parser made it up
+    pub span: Range<usize>,
 }
 
 impl Literal {
-    pub fn new(value: Value) -> Expr {
-        Expr::Literal(Literal { value })
+    pub fn new(value: Value, span: Range<usize>) -> Expr {
+        Expr::Literal(Literal { value, span })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Logical {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+}
+
+impl Logical {
+    pub fn new(left: Box<Expr>, operator: Token, right: Box<Expr>) -> Expr {
+        Expr::Logical(Logical { left, operator, right })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: Token,
+    pub value: Box<Expr>,
+}
+
+impl Set {
+    pub fn new(object: Box<Expr>, name: Token, value: Box<Expr>) -> Expr {
+        Expr::Set(Set { object, name, value })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Super {
+    pub id: usize,
+    pub keyword: Token,
+    pub method: Token,
+}
+
+impl Super {
+    pub fn new(keyword: Token, method: Token) -> Expr {
+        Expr::Super(Super { id: next_id(), keyword, method })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct This {
+    pub id: usize,
+    pub keyword: Token,
+}
+
+impl This {
+    pub fn new(keyword: Token) -> Expr {
+        Expr::This(This { id: next_id(), keyword })
     }
 }
 
@@ -94,3 +252,164 @@ impl Unary {
     }
 }
 
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub id: usize,
+    pub name: Token,
+}
+
+impl Variable {
+    pub fn new(name: Token) -> Expr {
+        Expr::Variable(Variable { id: next_id(), name })
+    }
+}
+
+pub trait StmtVisitor<T> {
+    fn visit_block_stmt(&self, block: &Block) -> T;
+    fn visit_class_stmt(&self, class: &Class) -> T;
+    fn visit_expression_stmt(&self, expression: &Expression) -> T;
+    fn visit_function_stmt(&self, function: &Function) -> T;
+    fn visit_if_stmt(&self, if_stmt: &If) -> T;
+    fn visit_print_stmt(&self, print: &Print) -> T;
+    fn visit_return_stmt(&self, return_stmt: &Return) -> T;
+    fn visit_var_stmt(&self, var: &Var) -> T;
+    fn visit_while_stmt(&self, while_stmt: &While) -> T;
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Block(Block),
+    Class(Class),
+    Expression(Expression),
+    Function(Function),
+    If(If),
+    Print(Print),
+    Return(Return),
+    Var(Var),
+    While(While),
+}
+
+impl Stmt {
+    pub fn accept<T>(&self, visitor: &dyn StmtVisitor<T>) -> T {
+        match self {
+            Stmt::Block(block) => visitor.visit_block_stmt(block),
+            Stmt::Class(class) => visitor.visit_class_stmt(class),
+            Stmt::Expression(expression) => visitor.visit_expression_stmt(expression),
+            Stmt::Function(function) => visitor.visit_function_stmt(function),
+            Stmt::If(if_stmt) => visitor.visit_if_stmt(if_stmt),
+            Stmt::Print(print) => visitor.visit_print_stmt(print),
+            Stmt::Return(return_stmt) => visitor.visit_return_stmt(return_stmt),
+            Stmt::Var(var) => visitor.visit_var_stmt(var),
+            Stmt::While(while_stmt) => visitor.visit_while_stmt(while_stmt),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub statements: Vec<Stmt>,
+}
+
+impl Block {
+    pub fn new(statements: Vec<Stmt>) -> Stmt {
+        Stmt::Block(Block { statements })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub name: Token,
+    pub superclass: Option<Box<Expr>>,
+    pub methods: Vec<Function>,
+}
+
+impl Class {
+    pub fn new(name: Token, superclass: Option<Box<Expr>>, methods: Vec<Function>) -> Stmt {
+        Stmt::Class(Class { name, superclass, methods })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expression {
+    pub expression: Box<Expr>,
+}
+
+impl Expression {
+    pub fn new(expression: Box<Expr>) -> Stmt {
+        Stmt::Expression(Expression { expression })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+impl Function {
+    pub fn new(name: Token, params: Vec<Token>, body: Vec<Stmt>) -> Stmt {
+        Stmt::Function(Function { name, params, body })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+impl If {
+    pub fn new(condition: Box<Expr>, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>>) -> Stmt {
+        Stmt::If(If { condition, then_branch, else_branch })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Print {
+    pub expression: Box<Expr>,
+}
+
+impl Print {
+    pub fn new(expression: Box<Expr>) -> Stmt {
+        Stmt::Print(Print { expression })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Return {
+    pub keyword: Token,
+    pub value: Option<Box<Expr>>,
+}
+
+impl Return {
+    pub fn new(keyword: Token, value: Option<Box<Expr>>) -> Stmt {
+        Stmt::Return(Return { keyword, value })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Var {
+    pub name: Token,
+    pub initializer: Option<Box<Expr>>,
+}
+
+impl Var {
+    pub fn new(name: Token, initializer: Option<Box<Expr>>) -> Stmt {
+        Stmt::Var(Var { name, initializer })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct While {
+    pub condition: Box<Expr>,
+    pub body: Box<Stmt>,
+}
+
+impl While {
+    pub fn new(condition: Box<Expr>, body: Box<Stmt>) -> Stmt {
+        Stmt::While(While { condition, body })
+    }
+}
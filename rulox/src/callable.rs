@@ -0,0 +1,75 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::ast::{Function, Value};
+use crate::class::LoxInstance;
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError, Unwind};
+use crate::lox::{Token, TokenType};
+
+/// anything that can sit on the left of a call expression
+pub trait LoxCallable: fmt::Debug + fmt::Display + Send + Sync {
+    /// how many arguments the call site has to pass
+    fn arity(&self) -> usize;
+    /// paren is the call's closing parenthesis, for callables that have no token of their own to blame
+    fn call(&self, interpreter: &Interpreter, paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError>;
+}
+
+/// a user defined function, together with the scope it was declared in
+#[derive(Debug)]
+pub struct LoxFunction {
+    declaration:    Function,
+    closure:        Arc<Mutex<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+
+    pub fn new(declaration: Function, closure: Arc<Mutex<Environment>>, is_initializer: bool) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// wraps the closure in a new scope where `this` is the given instance
+    pub fn bind(&self, instance: Arc<Mutex<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::new_enclosed(self.closure.clone());
+        environment.define("this", Value::Instance(instance));
+        LoxFunction::new(self.declaration.clone(), Arc::new(Mutex::new(environment)), self.is_initializer)
+    }
+
+    /// initializers always hand back the instance, even on an early `return;`
+    fn this(&self) -> Result<Value, RuntimeError> {
+        let name = &self.declaration.name;
+        let this = Token::new(TokenType::This, "this", Value::Null, name.line, name.span.clone(), name.column);
+        self.closure.lock().unwrap().get_at(0, &this)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(&self, interpreter: &Interpreter, _paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::new_enclosed(self.closure.clone());
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+
+        match interpreter.execute_block(&self.declaration.body, Arc::new(Mutex::new(environment))) {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => self.this(),
+            Ok(()) => Ok(Value::Null),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(e)) => Err(e),
+        }
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::ast::Value;
+use crate::callable::{LoxCallable, LoxFunction};
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::lox::Token;
+
+/// cheap to clone, every instance keeps a handle to its class for method lookups
+#[derive(Debug, Clone)]
+pub struct LoxClass {
+    pub name:   String,
+    superclass: Option<Arc<LoxClass>>,
+    methods:    Arc<HashMap<String, Arc<LoxFunction>>>,
+}
+
+impl LoxClass {
+
+    pub fn new(name: &str, superclass: Option<Arc<LoxClass>>, methods: HashMap<String, Arc<LoxFunction>>) -> Self {
+        LoxClass {
+            name:    name.to_string(),
+            superclass,
+            methods: Arc::new(methods),
+        }
+    }
+
+    /// looks in this class first, then up the inheritance chain
+    pub fn find_method(&self, name: &str) -> Option<Arc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+}
+
+impl LoxCallable for LoxClass {
+    /// a class takes whatever its initializer takes, or nothing without one
+    fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    fn call(&self, interpreter: &Interpreter, paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let instance = Arc::new(Mutex::new(LoxInstance::new(self.clone())));
+        if let Some(initializer) = self.find_method("init") {
+            initializer.bind(instance.clone()).call(interpreter, paren, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct LoxInstance {
+    class:  LoxClass,
+    fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+
+    pub fn new(class: LoxClass) -> Self {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// fields shadow methods, methods come back bound to this instance
+    pub fn get(instance: &Arc<Mutex<LoxInstance>>, name: &Token) -> Result<Value, RuntimeError> {
+        let (field, method) = {
+            let inner = instance.lock().unwrap();
+            (inner.fields.get(&name.lexeme).cloned(), inner.class.find_method(&name.lexeme))
+        };
+
+        if let Some(value) = field {
+            return Ok(value);
+        }
+        match method {
+            Some(method) => Ok(Value::Callable(Arc::new(method.bind(instance.clone())))),
+            None => Err(RuntimeError::new(name.clone(), &format!("Undefined property '{}'.", name.lexeme))),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
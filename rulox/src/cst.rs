@@ -0,0 +1,410 @@
+// the concrete syntax tree, which keeps every byte of the source, whitespace and comments included. The
+// parser only ever builds this, the interpreter runs what `lower` turns it into and tools like the formatter
+// work on it directly. It's two trees in the rowan style: the green tree is immutable and doesn't know where
+// it is, so an edit only rebuilds the nodes from the change up to the root and shares the rest, and the red
+// tree is made on demand on top of it, with offsets and parents
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::ast::Stmt;
+use crate::lox::{ScanDiagnostic, Scanner, TokenType};
+use crate::lower::Lowerer;
+use crate::parser::{ParseError, Parser};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    Program,
+    /// a declaration that didn't parse, along with what was skipped to get past it
+    Error,
+
+    VarDecl,
+    FunDecl,
+    ClassDecl,
+    /// the name, params and body of a function or method, after the `fun` if there is one
+    Function,
+    ParamList,
+    ExprStmt,
+    PrintStmt,
+    ReturnStmt,
+    IfStmt,
+    WhileStmt,
+    /// kept as written, it's only desugared into a while when lowering
+    ForStmt,
+    Block,
+
+    AssignExpr,
+    BinaryExpr,
+    LogicalExpr,
+    UnaryExpr,
+    CallExpr,
+    ArgList,
+    GetExpr,
+    GroupingExpr,
+    LiteralExpr,
+    VariableExpr,
+    ThisExpr,
+    SuperExpr,
+}
+
+impl SyntaxKind {
+
+    pub fn is_expr(&self) -> bool {
+        matches!(self,
+            SyntaxKind::AssignExpr | SyntaxKind::BinaryExpr | SyntaxKind::LogicalExpr | SyntaxKind::UnaryExpr |
+            SyntaxKind::CallExpr | SyntaxKind::GetExpr | SyntaxKind::GroupingExpr | SyntaxKind::LiteralExpr |
+            SyntaxKind::VariableExpr | SyntaxKind::ThisExpr | SyntaxKind::SuperExpr
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenToken {
+    pub kind: TokenType,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn width(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.width,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+/// a node and everything under it, without a position so the same node can turn up in more than one tree
+#[derive(Debug, Clone)]
+pub struct GreenNode {
+    pub kind:     SyntaxKind,
+    /// in bytes, the sum of its children's
+    pub width:    usize,
+    pub children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let width = children.iter().map(GreenElement::width).sum();
+        GreenNode {kind, width, children}
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = String::with_capacity(self.width);
+        self.write_text(&mut text);
+        text
+    }
+
+    fn write_text(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write_text(text),
+                GreenElement::Token(token) => text.push_str(&token.text),
+            }
+        }
+    }
+
+    /// a copy with one child swapped out, the others are shared rather than copied
+    pub fn replace_child(&self, index: usize, element: GreenElement) -> GreenNode {
+        let mut children = self.children.clone();
+        children[index] = element;
+        GreenNode::new(self.kind, children)
+    }
+}
+
+/// builds a green tree from the bottom up. A node is started before its first token, or afterwards at a
+/// checkpoint taken before it, which is how an infix expression wraps its left operand
+#[derive(Debug, Default)]
+pub struct GreenBuilder {
+    /// the open nodes, with where their children start in `children`
+    stack:    Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl GreenBuilder {
+
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push((kind, self.children.len()));
+    }
+
+    pub fn checkpoint(&self) -> usize {
+        self.children.len()
+    }
+
+    /// starts a node that takes everything added since the checkpoint as its first children
+    pub fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        self.stack.push((kind, checkpoint));
+    }
+
+    pub fn token(&mut self, kind: TokenType, text: &str) {
+        self.children.push(GreenElement::Token(Arc::new(GreenToken {kind, text: text.to_string()})));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, first) = self.stack.pop().expect("GreenBuilder: no node to finish");
+        let children = self.children.split_off(first);
+        self.children.push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    /// how many nodes are open
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// finishes every node opened since `depth`, for when parsing gave up partway through them
+    pub fn finish_to(&mut self, depth: usize) {
+        while self.stack.len() > depth {
+            self.finish_node();
+        }
+    }
+
+    /// the root, once every node has been finished
+    pub fn finish(mut self) -> GreenNode {
+        assert!(self.stack.is_empty(), "GreenBuilder: nodes still open");
+        match self.children.pop() {
+            Some(GreenElement::Node(node)) if self.children.is_empty() => Arc::unwrap_or_clone(node),
+            _ => panic!("GreenBuilder: there should be exactly one root node"),
+        }
+    }
+}
+
+struct NodeData {
+    green:  Arc<GreenNode>,
+    /// byte offset of its first child
+    offset: usize,
+    /// with the index of this node among its parent's children
+    parent: Option<(SyntaxNode, usize)>,
+}
+
+/// a green node at a place in the tree, cheap to clone
+#[derive(Clone)]
+pub struct SyntaxNode(Arc<NodeData>);
+
+impl SyntaxNode {
+
+    pub fn new_root(green: Arc<GreenNode>) -> Self {
+        Self::new_root_at(green, 0)
+    }
+
+    /// a root for source that comes after some other source, whose spans start at `offset`
+    pub fn new_root_at(green: Arc<GreenNode>, offset: usize) -> Self {
+        SyntaxNode(Arc::new(NodeData {green, offset, parent: None}))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    /// byte offsets into the source, leading trivia included
+    pub fn span(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.width
+    }
+
+    pub fn text(&self) -> String {
+        self.0.green.text()
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.as_ref().map(|(parent, _)| parent.clone())
+    }
+
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        self.0.green.children.iter().enumerate().map(|(index, child)| {
+            let element = match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Arc::new(NodeData {
+                    green:  green.clone(),
+                    offset,
+                    parent: Some((self.clone(), index)),
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green:  green.clone(),
+                    offset,
+                    parent: self.clone(),
+                }),
+            };
+            offset += child.width();
+            element
+        }).collect()
+    }
+
+    pub fn child_nodes(&self) -> Vec<SyntaxNode> {
+        self.children().into_iter().filter_map(SyntaxElement::into_node).collect()
+    }
+
+    /// the tokens directly under this node that aren't trivia
+    pub fn child_tokens(&self) -> Vec<SyntaxToken> {
+        self.children().into_iter()
+            .filter_map(SyntaxElement::into_token)
+            .filter(|token| !token.kind().is_trivia())
+            .collect()
+    }
+
+    /// every token under this node in source order, trivia included
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+        for child in self.children() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// a new tree with this node swapped for `replacement`, handing back the new root. Only the nodes
+    /// between here and the root are rebuilt, everything else is shared with the old tree
+    pub fn replace_with(&self, replacement: GreenNode) -> SyntaxNode {
+        match &self.0.parent {
+            None => SyntaxNode::new_root_at(Arc::new(replacement), self.0.offset),
+            Some((parent, index)) => {
+                let green = parent.green().replace_child(*index, GreenElement::Node(Arc::new(replacement)));
+                parent.replace_with(green)
+            },
+        }
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}@{:?}", self.kind(), self.span())
+    }
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green:  Arc<GreenToken>,
+    offset: usize,
+    parent: SyntaxNode,
+}
+
+impl SyntaxToken {
+
+    pub fn kind(&self) -> TokenType {
+        self.green.kind.clone()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}@{:?} {:?}", self.kind(), self.span(), self.text())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+
+    pub fn into_node(self) -> Option<SyntaxNode> {
+        match self {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        }
+    }
+
+    pub fn into_token(self) -> Option<SyntaxToken> {
+        match self {
+            SyntaxElement::Node(_) => None,
+            SyntaxElement::Token(token) => Some(token),
+        }
+    }
+}
+
+/// a whole source file as a cst, with whatever the scanner and parser had to say about it
+#[derive(Debug, Clone)]
+pub struct Parse {
+    pub root:             SyntaxNode,
+    pub scan_diagnostics: Vec<ScanDiagnostic>,
+    pub errors:           Vec<ParseError>,
+}
+
+impl Parse {
+
+    /// the statements the interpreter runs, less any that had errors
+    pub fn lower(&self) -> Vec<Stmt> {
+        Lowerer::new(&self.root).program()
+    }
+
+    /// the parse of the source with `range` replaced by `text`. When the edit is inside a block of a source
+    /// with no errors, only that block is scanned and parsed again and the rest of the tree is shared. When
+    /// the block doesn't come out whole and clean on its own, say the edit opened a string or took out a
+    /// `}`, the whole source is parsed again instead
+    pub fn reparse(&self, range: Range<usize>, text: &str) -> Parse {
+        let offset = self.root.span().start;
+        let mut source = self.root.text();
+        source.replace_range(range.start - offset..range.end - offset, text);
+
+        let clean = self.scan_diagnostics.is_empty() && self.errors.is_empty();
+        if let Some(block) = innermost_block(&self.root, &range).filter(|_| clean) {
+            let span = block.span();
+            let mut block_source = block.text();
+            block_source.replace_range(range.start - span.start..range.end - span.start, text);
+            if let Some(green) = reparse_block(&block_source) {
+                return Parse {root: block.replace_with(green), scan_diagnostics: vec![], errors: vec![]};
+            }
+        }
+        parse_at(&source, offset)
+    }
+}
+
+/// the deepest block whose braces are both outside the range, so the edit can't touch them
+fn innermost_block(node: &SyntaxNode, range: &Range<usize>) -> Option<SyntaxNode> {
+    let inner = node.child_nodes().into_iter()
+        .find(|child| child.span().start <= range.start && range.end <= child.span().end)
+        .and_then(|child| innermost_block(&child, range));
+    if inner.is_some() || node.kind() != SyntaxKind::Block {
+        return inner;
+    }
+
+    let tokens = node.child_tokens();
+    let (open, close) = (tokens.first()?, tokens.last()?);
+    let inside = open.span().end <= range.start && range.end <= close.span().start;
+    inside.then(|| node.clone())
+}
+
+/// the source of one block, leading trivia and all, parsed by itself. None unless it's exactly one block
+/// with nothing wrong in it
+fn reparse_block(source: &str) -> Option<GreenNode> {
+    let (tokens, scan_diagnostics) = Scanner::lossless(source).scan_tokens();
+    if !scan_diagnostics.is_empty() {return None};
+    Parser::new(tokens).parse_block()
+}
+
+/// never fails, anything that doesn't parse ends up in an Error node, so the tree's text is always the source
+pub fn parse(source: &str) -> Parse {
+    parse_at(source, 0)
+}
+
+/// for source that comes after some other source, like a line typed at the prompt, spans start at `offset`
+pub fn parse_at(source: &str, offset: usize) -> Parse {
+    let (tokens, scan_diagnostics) = Scanner::lossless(source).starting_at(offset).scan_tokens();
+    let (green, errors) = Parser::new(tokens).parse_tree();
+    Parse {root: SyntaxNode::new_root_at(Arc::new(green), offset), scan_diagnostics, errors}
+}
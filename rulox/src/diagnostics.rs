@@ -0,0 +1,369 @@
+// turns errors into text. The classic format is the book's one line per error, which its test suite and
+// testing/conformance.rs read. The rich one quotes the source with the span underlined, like rustc does,
+// and json is one object per line for editors and CI to read
+
+use std::ops::Range;
+
+use crate::lox::{Token, TokenType};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `[line N] Error at 'x': message`, and `message\n[line N]` for runtime errors
+    Classic,
+    Rich,
+    /// one object per line, see `json`
+    Json,
+}
+
+impl ErrorFormat {
+
+    /// the value of `--error-format=`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(ErrorFormat::Classic),
+            "rich" => Some(ErrorFormat::Rich),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// found before anything ran, by the scanner, parser or resolver
+    Compile,
+    Runtime,
+}
+
+/// a span of source with something to say about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span:    Range<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub phase:     Phase,
+    /// what kind of error it is, stable so tools can match on it, like `duplicate-variable`
+    pub code:      &'static str,
+    pub message:   String,
+    /// the line the classic format reports
+    pub line:      u32,
+    /// counted in chars from 1, for when the span can't be found in the source being rendered
+    pub column:    usize,
+    /// what the classic format puts between "Error" and the message, like ` at 'x'`
+    pub location:  String,
+    /// the span the error is about, underlined with carets
+    pub span:      Range<usize>,
+    /// written after the carets
+    pub label:     Option<String>,
+    /// other spans worth pointing at, underlined with dashes
+    pub secondary: Vec<Label>,
+    pub notes:     Vec<String>,
+    pub help:      Vec<String>,
+}
+
+impl Diagnostic {
+
+    /// an error the scanner found, which has no token to be at
+    pub fn at_span(line: u32, column: usize, span: Range<usize>, message: &str) -> Self {
+        Diagnostic {
+            phase:     Phase::Compile,
+            code:      "syntax",
+            message:   message.to_string(),
+            line,
+            column,
+            location:  String::new(),
+            span,
+            label:     None,
+            secondary: vec![],
+            notes:     vec![],
+            help:      vec![],
+        }
+    }
+
+    pub fn at_token(token: &Token, message: &str) -> Self {
+        let location = if matches!(token.type_of, TokenType::Eof) {
+            " at end".to_string()
+        } else {
+            format!(" at '{}'", token.lexeme)
+        };
+        Diagnostic {location, ..Self::at_span(token.line, token.column, token.span.clone(), message)}
+    }
+
+    pub fn runtime(token: &Token, message: &str) -> Self {
+        Diagnostic {phase: Phase::Runtime, code: "runtime", ..Self::at_span(token.line, token.column, token.span.clone(), message)}
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn with_label(mut self, message: &str) -> Self {
+        self.label = Some(message.to_string());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Range<usize>, message: &str) -> Self {
+        self.secondary.push(Label {span, message: message.to_string()});
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    pub format: ErrorFormat,
+    /// ansi colours, only wanted when a person is reading the terminal
+    pub colour: bool,
+}
+
+impl Renderer {
+
+    /// the diagnostic ready to write out, ending in a newline. `source` is what its spans point into and
+    /// `path` the file it came from, None for the prompt or source handed over as a string
+    pub fn render(&self, path: Option<&str>, source: &str, diagnostic: &Diagnostic) -> String {
+        match self.format {
+            ErrorFormat::Classic => classic(diagnostic),
+            ErrorFormat::Rich => self.rich(path, source, diagnostic),
+            ErrorFormat::Json => json(path, source, diagnostic),
+        }
+    }
+
+    fn paint(&self, colour: &str, text: &str) -> String {
+        if self.colour {format!("{}{}{}", colour, text, RESET)} else {text.to_string()}
+    }
+
+    fn rich(&self, path: Option<&str>, source: &str, diagnostic: &Diagnostic) -> String {
+        let heading = match diagnostic.phase {
+            Phase::Compile => "error",
+            Phase::Runtime => "runtime error",
+        };
+        let (line, column) = line_and_column(source, diagnostic);
+
+        // the primary label first, then the secondary ones in the order they appear. A span that isn't in
+        // this source can't be quoted, only the heading is left when it's the primary one
+        let mut marks: Vec<(usize, Range<usize>, Option<&str>, bool)> = vec![];
+        if within(source, &diagnostic.span) {
+            marks.push((line, diagnostic.span.clone(), diagnostic.label.as_deref(), true));
+            let mut secondary: Vec<&Label> = diagnostic.secondary.iter().filter(|label| within(source, &label.span)).collect();
+            secondary.sort_by_key(|label| label.span.start);
+            for label in secondary {
+                marks.push((position(source, label.span.start).0, label.span.clone(), Some(&label.message), false));
+            }
+        }
+        let mut lines: Vec<usize> = marks.iter().map(|(line, ..)| *line).collect();
+        lines.sort();
+        lines.dedup();
+
+        let width = lines.last().map_or(line, |last| *last).to_string().len();
+        let gutter = self.paint(BLUE, &format!("{} |", " ".repeat(width)));
+
+        let mut out = format!("{}{}\n", self.paint(RED, heading), self.paint(BOLD, &format!(": {}", diagnostic.message)));
+        let location = match path {
+            Some(path) => format!("{}:{}:{}", path, line, column),
+            None => format!("line {}, column {}", line, column),
+        };
+        out.push_str(&format!("{}{} {}\n", " ".repeat(width), self.paint(BLUE, "-->"), location));
+        if !lines.is_empty() {
+            out.push_str(&format!("{}\n", gutter));
+        }
+
+        let mut previous: Option<usize> = None;
+        for line in lines {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                out.push_str(&format!("{}\n", self.paint(BLUE, "...")));
+            }
+            previous = Some(line);
+
+            let (start, text) = line_at(source, line);
+            let number = self.paint(BLUE, &format!("{:>width$} |", line, width = width));
+            out.push_str(&format!("{}\n", format!("{} {}", number, text).trim_end()));
+
+            let mut here: Vec<_> = marks.iter().filter(|(at, ..)| *at == line).collect();
+            here.sort_by_key(|(_, span, ..)| span.start);
+            for (_, span, label, primary) in here {
+                out.push_str(&format!("{} {}\n", gutter, self.underline(text, span.start - start, span.end.saturating_sub(start), *label, *primary)));
+            }
+        }
+
+        if !diagnostic.notes.is_empty() || !diagnostic.help.is_empty() {
+            out.push_str(&format!("{}\n", gutter));
+        }
+        for note in &diagnostic.notes {
+            out.push_str(&format!("{} {} note: {}\n", " ".repeat(width), self.paint(BLUE, "="), note));
+        }
+        for help in &diagnostic.help {
+            out.push_str(&format!("{} {} help: {}\n", " ".repeat(width), self.paint(BLUE, "="), help));
+        }
+        out
+    }
+
+    /// carets or dashes under start..end of the line, cut off at the end of the line for spans that run on.
+    /// Empty spans still get one mark, so the end of the file can be pointed at
+    fn underline(&self, text: &str, start: usize, end: usize, label: Option<&str>, primary: bool) -> String {
+        let start = start.min(text.len());
+        let end = end.clamp(start, text.len());
+        // tabs are kept so the marks line up however wide the terminal draws them
+        let padding: String = text[..start].chars().map(|c| if c == '\t' {'\t'} else {' '}).collect();
+        let marks = text[start..end].chars().count().max(1);
+
+        let (mark, colour) = if primary {("^", RED)} else {("-", BLUE)};
+        let mut underline = format!("{}{}", padding, self.paint(colour, &mark.repeat(marks)));
+        if let Some(label) = label {
+            underline.push(' ');
+            underline.push_str(&self.paint(colour, label));
+        }
+        underline.trim_end().to_string()
+    }
+}
+
+/// everything one Lox has run, each source after the one before, so the spans of something an earlier run
+/// left behind, like a function the prompt defined a few lines back, still point at the text they came from
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    text:   String,
+    /// where each run's source starts in `text`
+    starts: Vec<usize>,
+}
+
+impl SourceMap {
+
+    /// keeps a run's source, handing back the offset its spans should start from
+    pub fn add(&mut self, source: &str) -> usize {
+        let start = self.text.len();
+        self.text.push_str(source);
+        self.starts.push(start);
+        start
+    }
+
+    /// the source the diagnostic's span is in, with the diagnostic's spans made relative to it. Secondary
+    /// labels pointing into some other run's source are left out
+    pub fn locate(&self, diagnostic: &Diagnostic) -> (&str, Diagnostic) {
+        let run = self.starts.partition_point(|&start| start <= diagnostic.span.start).max(1) - 1;
+        let start = self.starts.get(run).copied().unwrap_or(0);
+        let end = self.starts.get(run + 1).copied().unwrap_or(self.text.len());
+        let relative = |span: &Range<usize>| span.start.saturating_sub(start)..span.end.saturating_sub(start);
+
+        let mut located = diagnostic.clone();
+        located.span = relative(&diagnostic.span);
+        located.secondary = diagnostic.secondary.iter()
+            .filter(|label| (start..=end).contains(&label.span.start) && label.span.end <= end)
+            .map(|label| Label {span: relative(&label.span), message: label.message.clone()})
+            .collect();
+        (&self.text[start..end], located)
+    }
+}
+
+fn classic(diagnostic: &Diagnostic) -> String {
+    match diagnostic.phase {
+        Phase::Compile => format!("[line {}] Error{}: {}\n", diagnostic.line, diagnostic.location, diagnostic.message),
+        Phase::Runtime => format!("{}\n[line {}]\n", diagnostic.message, diagnostic.line),
+    }
+}
+
+/// `{"severity":"error","code":...,"message":...,"file":...,"line":...,"column":...,"span":{"start":...,"end":...}}`
+/// on one line, plus the phase, the secondary labels, notes and help. Line and column are where the span
+/// starts, counting chars from 1, and spans are byte offsets into the file
+fn json(path: Option<&str>, source: &str, diagnostic: &Diagnostic) -> String {
+    let phase = match diagnostic.phase {
+        Phase::Compile => "compile",
+        Phase::Runtime => "runtime",
+    };
+    let (line, column) = line_and_column(source, diagnostic);
+    let strings = |items: &[String]| items.iter().map(|item| json_string(item)).collect::<Vec<_>>().join(",");
+    let labels = diagnostic.secondary.iter()
+        .filter(|label| within(source, &label.span))
+        .map(|label| {
+            let (line, column) = position(source, label.span.start);
+            format!(
+                "{{\"message\":{},\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}}}}",
+                json_string(&label.message), line, column, label.span.start, label.span.end,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "{{\"severity\":\"error\",\"phase\":\"{}\",\"code\":{},\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}},\"labels\":[{}],\"notes\":[{}],\"help\":[{}]}}\n",
+        phase,
+        json_string(diagnostic.code),
+        json_string(&diagnostic.message),
+        path.map_or("null".to_string(), json_string),
+        line,
+        column,
+        diagnostic.span.start,
+        diagnostic.span.end,
+        labels,
+        strings(&diagnostic.notes),
+        strings(&diagnostic.help),
+    )
+}
+
+/// quoted, with everything json doesn't allow raw in a string escaped
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// where the diagnostic's span starts, or where its token said it was when the span isn't in this source
+fn line_and_column(source: &str, diagnostic: &Diagnostic) -> (usize, usize) {
+    if within(source, &diagnostic.span) {
+        position(source, diagnostic.span.start)
+    } else {
+        (diagnostic.line as usize, diagnostic.column)
+    }
+}
+
+/// whether a span can be quoted from the source
+fn within(source: &str, span: &Range<usize>) -> bool {
+    span.start <= span.end && source.get(span.clone()).is_some()
+}
+
+/// line and column, both counted from 1, of a byte offset
+pub fn position(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// where the line starts and its text, without the newline
+fn line_at(source: &str, line: usize) -> (usize, &str) {
+    let mut start = 0;
+    for (index, text) in source.split('\n').enumerate() {
+        if index + 1 == line {
+            return (start, text.strip_suffix('\r').unwrap_or(text));
+        }
+        start += text.len() + 1;
+    }
+    (source.len(), "")
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::ast::Value;
+use crate::interpreter::RuntimeError;
+use crate::lox::Token;
+
+/// a single scope of variable bindings, chained to the scope that encloses it
+#[derive(Debug, Default)]
+pub struct Environment {
+    values:    HashMap<String, Value>,
+    enclosing: Option<Arc<Mutex<Environment>>>,
+}
+
+impl Environment {
+
+    pub fn new() -> Self {
+        Environment {
+            values:    HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn new_enclosed(enclosing: Arc<Mutex<Environment>>) -> Self {
+        Environment {
+            values:    HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// binds a name in this scope, redefining it if it already exists
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// looks a name up in this scope, then walks outwards through the enclosing ones
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(v) = self.values.get(&name.lexeme) {
+            return Ok(v.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.lock().unwrap().get(name),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    /// assigns to an existing binding, it's an error if the name was never defined
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(v) = self.values.get_mut(&name.lexeme) {
+            *v = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.lock().unwrap().assign(name, value),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    /// reads a binding exactly `distance` scopes out, as worked out by the resolver
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            return self.values.get(&name.lexeme).cloned().ok_or_else(|| Self::undefined(name));
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.lock().unwrap().get_at(distance - 1, name),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), value);
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.lock().unwrap().assign_at(distance - 1, name, value),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    fn undefined(name: &Token) -> RuntimeError {
+        RuntimeError::new(name.clone(), &format!("Undefined variable '{}'.", name.lexeme))
+    }
+}
@@ -0,0 +1,384 @@
+// `rulox fmt`, which reprints a file from its cst in one canonical style. Only whitespace changes, the
+// tokens and comments come out the same and in the same order, and formatting formatted source changes nothing
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+
+use crate::cst::{self, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::diagnostics::{Diagnostic, ErrorFormat, Renderer};
+use crate::lox::TokenType;
+
+/// calls whose arguments would run past this many columns get one argument per line
+pub const WIDTH: usize = 100;
+const INDENT: &str = "  ";
+
+const USAGE: &str = "Usage: rulox fmt [--check] [--error-format=rich|classic|json] [files...]";
+
+/// formats each file in place, or only says which would change with `--check`. With no files it formats
+/// stdin to stdout. The exit code is 1 when `--check` found something to change, 65 if a file didn't parse
+pub fn main(args: &[String]) -> i32 {
+    let (flags, paths): (Vec<&String>, Vec<&String>) = args.iter().partition(|arg| arg.starts_with("--"));
+    let mut check = false;
+    let mut renderer = Renderer {format: ErrorFormat::Rich, colour: io::stderr().is_terminal()};
+    for flag in flags {
+        if flag == "--check" {
+            check = true;
+        } else if let Some(format) = flag.strip_prefix("--error-format=").and_then(ErrorFormat::parse) {
+            renderer.format = format;
+        } else {
+            eprintln!("{}", USAGE);
+            return 64;
+        }
+    }
+
+    if paths.is_empty() {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).expect("Fmt: could not read stdin");
+        return match format(&source) {
+            Ok(formatted) if check => i32::from(formatted != source),
+            Ok(formatted) => {
+                print!("{}", formatted);
+                0
+            },
+            Err(diagnostics) => report(&renderer, None, &source, &diagnostics),
+        };
+    }
+
+    let mut exit_code = 0;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                exit_code = 66;
+                continue;
+            },
+        };
+        match format(&source) {
+            Ok(formatted) if formatted == source => {},
+            Ok(_) if check => {
+                println!("{} would be reformatted", path);
+                exit_code = exit_code.max(1);
+            },
+            Ok(formatted) => fs::write(path, formatted).expect("Fmt: could not write file"),
+            Err(diagnostics) => exit_code = exit_code.max(report(&renderer, Some(path), &source, &diagnostics)),
+        }
+    }
+    exit_code
+}
+
+fn report(renderer: &Renderer, path: Option<&str>, source: &str, diagnostics: &[Diagnostic]) -> i32 {
+    for diagnostic in diagnostics {
+        eprint!("{}", renderer.render(path, source, diagnostic));
+    }
+    65
+}
+
+/// the source in canonical style, or what's wrong with it when it doesn't parse, since there's no telling
+/// what a broken file was meant to look like
+pub fn format(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let parse = cst::parse(source);
+    let mut diagnostics: Vec<Diagnostic> = parse.scan_diagnostics.iter().map(|diagnostic| diagnostic.to_diagnostic()).collect();
+    diagnostics.extend(parse.errors.iter().map(|error| Diagnostic::at_token(&error.token, &error.error)));
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let mut formatter = Formatter::new(&parse.root);
+    formatter.program(&parse.root);
+    Ok(formatter.out)
+}
+
+/// what goes between a token and the one before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sep {
+    Nothing,
+    Space,
+    /// a new line, like before a `}`
+    Line,
+    /// a new line that keeps one blank line before it if the source had any, between statements
+    Statement,
+}
+
+/// the comments in front of a token, each with how many newlines came before it, and the newlines after
+/// the last of them
+#[derive(Debug, Default)]
+struct Leading {
+    comments: Vec<(String, usize)>,
+    newlines: usize,
+}
+
+struct Formatter {
+    out:         String,
+    indent:      usize,
+    /// chars since the last newline
+    column:      usize,
+    line_start:  bool,
+    /// the last thing written was a `{`, which is never followed by a blank line
+    after_brace: bool,
+    /// the last token written
+    previous:    Option<TokenType>,
+    /// measuring how long something is on one line, so nothing wraps
+    flat:        bool,
+    /// by where the token they come before starts, taken out as they're written
+    leading:     HashMap<usize, Leading>,
+}
+
+impl Formatter {
+
+    fn new(root: &SyntaxNode) -> Self {
+        let mut leading: HashMap<usize, Leading> = HashMap::new();
+        let mut pending = Leading::default();
+        for token in root.tokens() {
+            match token.kind() {
+                TokenType::Whitespace => pending.newlines += token.text().matches('\n').count(),
+                TokenType::Comment => {
+                    pending.comments.push((token.text().trim_end().to_string(), pending.newlines));
+                    pending.newlines = 0;
+                },
+                _ => {leading.insert(token.span().start, std::mem::take(&mut pending));},
+            }
+        }
+        Formatter {
+            out:         String::new(),
+            indent:      0,
+            column:      0,
+            line_start:  true,
+            after_brace: false,
+            previous:    None,
+            flat:        false,
+            leading,
+        }
+    }
+
+    fn program(&mut self, root: &SyntaxNode) {
+        for element in significant(root) {
+            match element {
+                SyntaxElement::Node(node) => self.stmt(&node, Sep::Statement),
+                SyntaxElement::Token(eof) => self.comments(&eof),
+            }
+        }
+        if !self.line_start {self.end_line()};
+    }
+
+    /// everything in a statement is a space apart, except that nothing goes before a `;` or `)` or after a `(`
+    fn stmt(&mut self, node: &SyntaxNode, first: Sep) {
+        let elements = significant(node);
+        if node.kind() == SyntaxKind::Block {
+            return self.braced(&elements, first);
+        }
+
+        for (index, element) in elements.iter().enumerate() {
+            let sep = match element {
+                _ if index == 0 => first,
+                SyntaxElement::Token(token) if matches!(token.kind(), TokenType::Semicolon | TokenType::RightParen) => Sep::Nothing,
+                _ if self.previous == Some(TokenType::LeftParen) => Sep::Nothing,
+                _ => Sep::Space,
+            };
+            match element {
+                SyntaxElement::Token(token) if token.kind() == TokenType::LeftBrace => {
+                    // a class body, which is laid out like a block of methods
+                    return self.braced(&elements[index..], sep);
+                },
+                SyntaxElement::Token(token) => self.token(token, sep),
+                SyntaxElement::Node(child) if child.kind().is_expr() => self.expr(child, sep),
+                SyntaxElement::Node(child) if child.kind() == SyntaxKind::Function => self.function(child, sep),
+                SyntaxElement::Node(child) => self.stmt(child, sep),
+            }
+        }
+    }
+
+    /// from a `{` to its `}`, with what's between a line each and indented. Nothing between stays `{}`
+    fn braced(&mut self, elements: &[SyntaxElement], first: Sep) {
+        for (index, element) in elements.iter().enumerate() {
+            match element {
+                SyntaxElement::Token(token) if token.kind() == TokenType::LeftBrace => {
+                    // comments between a header and its `{` would push the `{` onto a line of its own, so they
+                    // go after it instead, the first on the `{`'s line. A block on its own starts a line anyway
+                    let mut moved = self.leading.get_mut(&token.span().start)
+                        .filter(|_| first == Sep::Space)
+                        .map(|leading| std::mem::take(&mut leading.comments))
+                        .unwrap_or_default();
+                    self.token(token, first);
+                    self.indent += 1;
+                    let next = elements.get(index + 1).and_then(first_token);
+                    if let (Some((_, newlines)), Some(next)) = (moved.first_mut(), next) {
+                        *newlines = 0;
+                        let leading = self.leading.entry(next.span().start).or_default();
+                        moved.append(&mut leading.comments);
+                        leading.comments = moved;
+                    }
+                },
+                SyntaxElement::Token(token) if token.kind() == TokenType::RightBrace => {
+                    let empty = self.after_brace && self.leading.get(&token.span().start)
+                        .is_none_or(|leading| leading.comments.is_empty());
+                    self.comments(token);
+                    self.indent -= 1;
+                    self.token(token, if empty {Sep::Nothing} else {Sep::Line});
+                },
+                SyntaxElement::Token(token) => self.token(token, Sep::Space),
+                SyntaxElement::Node(child) if child.kind() == SyntaxKind::Function => self.function(child, Sep::Statement),
+                SyntaxElement::Node(child) => self.stmt(child, Sep::Statement),
+            }
+        }
+    }
+
+    /// `name(a, b) {`, after the `fun` if there is one
+    fn function(&mut self, node: &SyntaxNode, first: Sep) {
+        for element in significant(node) {
+            match element {
+                SyntaxElement::Token(name) => self.token(&name, first),
+                SyntaxElement::Node(params) if params.kind() == SyntaxKind::ParamList => {
+                    for token in params.child_tokens() {
+                        let sep = if self.previous == Some(TokenType::Comma) {Sep::Space} else {Sep::Nothing};
+                        self.token(&token, sep);
+                    }
+                },
+                SyntaxElement::Node(body) => self.stmt(&body, Sep::Space),
+            }
+        }
+    }
+
+    /// operators are spaced out, everything else sticks together
+    fn expr(&mut self, node: &SyntaxNode, first: Sep) {
+        let spaced = matches!(node.kind(), SyntaxKind::AssignExpr | SyntaxKind::BinaryExpr | SyntaxKind::LogicalExpr);
+        for (index, element) in significant(node).into_iter().enumerate() {
+            let sep = match index {
+                0 => first,
+                _ if spaced => Sep::Space,
+                _ => Sep::Nothing,
+            };
+            match element {
+                SyntaxElement::Node(child) if child.kind() == SyntaxKind::ArgList => self.arguments(&child),
+                SyntaxElement::Node(child) => self.expr(&child, sep),
+                SyntaxElement::Token(token) => self.token(&token, sep),
+            }
+        }
+    }
+
+    /// `(a, b)`, or one argument per line when the `)` would end up past WIDTH
+    fn arguments(&mut self, node: &SyntaxNode) {
+        let elements = significant(node);
+        let wrap = !self.flat && elements.len() > 2 && self.column + self.measure(node) > WIDTH;
+
+        for element in &elements {
+            match element {
+                SyntaxElement::Token(token) if token.kind() == TokenType::LeftParen => {
+                    self.token(token, Sep::Nothing);
+                    if wrap {self.indent += 1};
+                },
+                SyntaxElement::Token(token) if token.kind() == TokenType::RightParen && wrap => {
+                    self.comments(token);
+                    self.indent -= 1;
+                    self.token(token, Sep::Line);
+                },
+                SyntaxElement::Token(token) => self.token(token, Sep::Nothing),
+                SyntaxElement::Node(argument) => {
+                    let sep = match self.previous {
+                        _ if wrap => Sep::Line,
+                        Some(TokenType::Comma) => Sep::Space,
+                        _ => Sep::Nothing,
+                    };
+                    self.expr(argument, sep);
+                },
+            }
+        }
+    }
+
+    /// how many columns the arguments take up on one line
+    fn measure(&self, node: &SyntaxNode) -> usize {
+        let mut flat = Formatter {
+            out:         String::new(),
+            indent:      0,
+            column:      0,
+            line_start:  false,
+            after_brace: false,
+            previous:    None,
+            flat:        true,
+            leading:     HashMap::new(),
+        };
+        flat.arguments(node);
+        flat.out.chars().count()
+    }
+
+    /// writes the comments in front of the token. One that was on the same line as the token before it stays
+    /// there, the rest get lines of their own
+    fn comments(&mut self, token: &SyntaxToken) {
+        let Some(leading) = self.leading.get_mut(&token.span().start) else {return};
+        for (comment, newlines) in std::mem::take(&mut leading.comments) {
+            if newlines == 0 && !self.line_start {
+                self.write(" ");
+            } else {
+                if !self.line_start {self.end_line()};
+                if newlines >= 2 {self.blank_line()};
+                self.write(&INDENT.repeat(self.indent));
+            }
+            self.write(&comment);
+            self.after_brace = false;
+            self.end_line();
+        }
+    }
+
+    fn token(&mut self, token: &SyntaxToken, sep: Sep) {
+        self.comments(token);
+        let newlines = self.leading.remove(&token.span().start).map_or(0, |leading| leading.newlines);
+
+        match sep {
+            Sep::Line | Sep::Statement => {
+                if !self.line_start {self.end_line()};
+                if sep == Sep::Statement && newlines >= 2 {self.blank_line()};
+                self.write(&INDENT.repeat(self.indent));
+            },
+            // pushed onto a new line by a comment, so it carries on the line before
+            _ if self.line_start && !self.out.is_empty() => self.write(&INDENT.repeat(self.indent + 1)),
+            Sep::Space => self.write(" "),
+            Sep::Nothing => {},
+        }
+        self.write(token.text());
+        self.after_brace = token.kind() == TokenType::LeftBrace;
+        self.previous = Some(token.kind());
+    }
+
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+        match text.rfind('\n') {
+            Some(newline) => self.column = text[newline + 1..].chars().count(),
+            None => self.column += text.chars().count(),
+        }
+        self.line_start = false;
+    }
+
+    fn end_line(&mut self) {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.column = 0;
+        self.line_start = true;
+    }
+
+    /// at most one in a row, and never at the top of the file or straight after a `{`
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.after_brace && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+}
+
+/// the first token of an element that isn't trivia, which is the one its comments are kept under
+fn first_token(element: &SyntaxElement) -> Option<SyntaxToken> {
+    match element {
+        SyntaxElement::Token(token) => Some(token.clone()),
+        SyntaxElement::Node(node) => node.tokens().into_iter().find(|token| !token.kind().is_trivia()),
+    }
+}
+
+/// the children of a node other than trivia
+fn significant(node: &SyntaxNode) -> Vec<SyntaxElement> {
+    node.children().into_iter()
+        .filter(|element| match element {
+            SyntaxElement::Token(token) => !token.kind().is_trivia(),
+            SyntaxElement::Node(_) => true,
+        })
+        .collect()
+}
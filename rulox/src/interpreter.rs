@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::ast::{Assign, Binary, Block, Call, Class, Expr, Expression, Function, Get, Grouping, If, Literal, Logical, Print, Return, Set, Stmt, StmtVisitor, Super, This, Unary, Value, Var, Variable, Visitor, While};
+use crate::callable::{LoxCallable, LoxFunction};
+use crate::class::{LoxClass, LoxInstance};
+use crate::environment::Environment;
+use crate::lox::{Output, Token, TokenType};
+use crate::native::{self, NativeFunction};
+
+pub struct Interpreter {
+    pub globals: Arc<Mutex<Environment>>,
+    environment: Mutex<Arc<Mutex<Environment>>>,
+    locals:      Mutex<HashMap<usize, usize>>,
+    /// where print writes to
+    stdout:      Output,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+
+    pub fn new() -> Self {
+        Self::with_output(Arc::new(Mutex::new(io::stdout())))
+    }
+
+    /// same as new, but print goes to the given writer instead of stdout
+    pub fn with_output(stdout: Output) -> Self {
+        let globals = Arc::new(Mutex::new(Environment::new()));
+        let interpreter = Interpreter {
+            environment: Mutex::new(globals.clone()),
+            globals,
+            locals:      Mutex::new(HashMap::new()),
+            stdout,
+        };
+        interpreter.define_native("clock", 0, native::clock);
+        interpreter
+    }
+
+    /// exposes a rust function to scripts as a global, errors it returns become runtime errors at the call
+    pub fn define_native<F>(&self, name: &str, arity: usize, function: F)
+    where F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static {
+        let native = NativeFunction::new(name, arity, function);
+        self.globals.lock().unwrap().define(name, Value::Callable(Arc::new(native)));
+    }
+
+    pub fn interpret(&self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            match self.execute(statement) {
+                Ok(()) => {},
+                Err(Unwind::Error(e)) => return Err(e),
+                Err(Unwind::Return(_)) => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&self, stmt: &Stmt) -> Result<(), Unwind> {
+        stmt.accept(self)
+    }
+
+    /// runs the statements inside the given scope, restoring the previous one afterwards
+    pub fn execute_block(&self, statements: &[Stmt], environment: Arc<Mutex<Environment>>) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut *self.environment.lock().unwrap(), environment);
+        let result = statements.iter().try_for_each(|statement| self.execute(statement));
+        *self.environment.lock().unwrap() = previous;
+        result
+    }
+
+    /// called by the resolver for every local, depth being how many scopes out the binding lives
+    pub fn resolve(&self, id: usize, depth: usize) {
+        self.locals.lock().unwrap().insert(id, depth);
+    }
+
+    /// anything the resolver didn't see in a local scope is assumed to be a global
+    fn look_up_variable(&self, name: &Token, id: usize) -> Result<Value, RuntimeError> {
+        let distance: Option<usize> = self.locals.lock().unwrap().get(&id).copied();
+        match distance {
+            Some(distance) => self.current_environment().lock().unwrap().get_at(distance, name),
+            None => self.globals.lock().unwrap().get(name),
+        }
+    }
+
+    fn current_environment(&self) -> Arc<Mutex<Environment>> {
+        self.environment.lock().unwrap().clone()
+    }
+
+    fn evaluate(&self, expr: &Expr) -> Result<Value, RuntimeError> {
+        expr.accept(self)
+    }
+
+    /// false and nil are falsey, everything else is truthy
+    fn is_truthy(value: &Value) -> bool {
+        match value {
+            Value::Null => false,
+            Value::Boolean(b) => *b,
+            _ => true,
+        }
+    }
+
+    /// integers and floats compare by their numeric value
+    fn is_equal(a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::Null, Value::Null) => true,
+            (Value::Boolean(x), Value::Boolean(y)) => x == y,
+            (Value::String(x), Value::String(y)) => x == y,
+            (Value::Callable(x), Value::Callable(y)) => Arc::ptr_eq(x, y),
+            (Value::Class(x), Value::Class(y)) => Arc::ptr_eq(x, y),
+            (Value::Instance(x), Value::Instance(y)) => Arc::ptr_eq(x, y),
+            _ => match (Self::as_number(a), Self::as_number(b)) {
+                (Some(x), Some(y)) => x == y,
+                _ => false,
+            },
+        }
+    }
+
+    fn as_number(value: &Value) -> Option<f64> {
+        match value {
+            Value::Float(f) => Some(*f),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    fn number_operand(operator: &Token, operand: &Value) -> Result<f64, RuntimeError> {
+        Self::as_number(operand)
+            .ok_or_else(|| RuntimeError::new(operator.clone(), "Operand must be a number."))
+    }
+
+    fn number_operands(operator: &Token, left: &Value, right: &Value) -> Result<(f64, f64), RuntimeError> {
+        match (Self::as_number(left), Self::as_number(right)) {
+            (Some(l), Some(r)) => Ok((l, r)),
+            _ => Err(RuntimeError::new(operator.clone(), "Operands must be numbers.")),
+        }
+    }
+}
+
+impl Visitor<Result<Value, RuntimeError>> for Interpreter {
+    fn visit_assign(&self, assign: &Assign) -> Result<Value, RuntimeError> {
+        let value: Value = self.evaluate(&assign.value)?;
+        let distance: Option<usize> = self.locals.lock().unwrap().get(&assign.id).copied();
+        match distance {
+            Some(distance) => self.current_environment().lock().unwrap().assign_at(distance, &assign.name, value.clone())?,
+            None => self.globals.lock().unwrap().assign(&assign.name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_binary(&self, binary: &Binary) -> Result<Value, RuntimeError> {
+        let left: Value = self.evaluate(&binary.left)?;
+        let right: Value = self.evaluate(&binary.right)?;
+        let op: &Token = &binary.operator;
+
+        match op.type_of {
+            TokenType::Minus => {
+                let (l, r) = Self::number_operands(op, &left, &right)?;
+                Ok(Value::Float(l - r))
+            },
+            TokenType::Slash => {
+                let (l, r) = Self::number_operands(op, &left, &right)?;
+                Ok(Value::Float(l / r))
+            },
+            TokenType::Star => {
+                let (l, r) = Self::number_operands(op, &left, &right)?;
+                Ok(Value::Float(l * r))
+            },
+            TokenType::Plus => {
+                match (&left, &right) {
+                    (Value::String(l), Value::String(r)) => Ok(Value::String(format!("{}{}", l, r))),
+                    _ => match (Self::as_number(&left), Self::as_number(&right)) {
+                        (Some(l), Some(r)) => Ok(Value::Float(l + r)),
+                        _ => Err(RuntimeError::new(op.clone(), "Operands must be two numbers or two strings.")),
+                    },
+                }
+            },
+            TokenType::Greater => {
+                let (l, r) = Self::number_operands(op, &left, &right)?;
+                Ok(Value::Boolean(l > r))
+            },
+            TokenType::GreaterEqual => {
+                let (l, r) = Self::number_operands(op, &left, &right)?;
+                Ok(Value::Boolean(l >= r))
+            },
+            TokenType::Less => {
+                let (l, r) = Self::number_operands(op, &left, &right)?;
+                Ok(Value::Boolean(l < r))
+            },
+            TokenType::LessEqual => {
+                let (l, r) = Self::number_operands(op, &left, &right)?;
+                Ok(Value::Boolean(l <= r))
+            },
+            TokenType::BangEqual  => Ok(Value::Boolean(!Self::is_equal(&left, &right))),
+            TokenType::EqualEqual => Ok(Value::Boolean(Self::is_equal(&left, &right))),
+            _ => Err(RuntimeError::new(op.clone(), "Unknown binary operator.")),
+        }
+    }
+
+    fn visit_call(&self, call: &Call) -> Result<Value, RuntimeError> {
+        let callee: Value = self.evaluate(&call.callee)?;
+
+        let mut arguments: Vec<Value> = vec![];
+        for argument in &call.arguments {
+            arguments.push(self.evaluate(argument)?);
+        }
+
+        let function: Arc<dyn LoxCallable> = match callee {
+            Value::Callable(c) => c,
+            Value::Class(c) => c,
+            _ => return Err(RuntimeError::new(call.paren.clone(), "Can only call functions and classes.")),
+        };
+        if arguments.len() != function.arity() {
+            return Err(RuntimeError::new(
+                call.paren.clone(),
+                &format!("Expected {} arguments but got {}.", function.arity(), arguments.len())
+            ));
+        }
+        function.call(self, &call.paren, arguments)
+    }
+
+    fn visit_get(&self, get: &Get) -> Result<Value, RuntimeError> {
+        match self.evaluate(&get.object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, &get.name),
+            _ => Err(RuntimeError::new(get.name.clone(), "Only instances have properties.")),
+        }
+    }
+
+    fn visit_grouping(&self, grouping: &Grouping) -> Result<Value, RuntimeError> {
+        self.evaluate(&grouping.expression)
+    }
+
+    fn visit_literal(&self, literal: &Literal) -> Result<Value, RuntimeError> {
+        Ok(literal.value.clone())
+    }
+
+    /// short-circuits, handing back whichever operand decided the result
+    fn visit_logical(&self, logical: &Logical) -> Result<Value, RuntimeError> {
+        let left: Value = self.evaluate(&logical.left)?;
+
+        if logical.operator.type_of == TokenType::Or {
+            if Self::is_truthy(&left) {return Ok(left)};
+        } else if !Self::is_truthy(&left) {
+            return Ok(left);
+        }
+        self.evaluate(&logical.right)
+    }
+
+    fn visit_set(&self, set: &Set) -> Result<Value, RuntimeError> {
+        let Value::Instance(instance) = self.evaluate(&set.object)? else {
+            return Err(RuntimeError::new(set.name.clone(), "Only instances have fields."));
+        };
+        let value: Value = self.evaluate(&set.value)?;
+        instance.lock().unwrap().set(&set.name, value.clone());
+        Ok(value)
+    }
+
+    /// `super` lives one scope out from the `this` scope the method was bound in
+    fn visit_super(&self, super_expr: &Super) -> Result<Value, RuntimeError> {
+        let distance: usize = match self.locals.lock().unwrap().get(&super_expr.id) {
+            Some(distance) => *distance,
+            None => return Err(RuntimeError::new(super_expr.keyword.clone(), "Can't use 'super' outside of a class.")),
+        };
+        let environment = self.current_environment();
+
+        let Value::Class(superclass) = environment.lock().unwrap().get_at(distance, &super_expr.keyword)? else {
+            return Err(RuntimeError::new(super_expr.keyword.clone(), "Superclass must be a class."));
+        };
+        let keyword = &super_expr.keyword;
+        let this = Token::new(TokenType::This, "this", Value::Null, keyword.line, keyword.span.clone(), keyword.column);
+        let Value::Instance(object) = environment.lock().unwrap().get_at(distance - 1, &this)? else {
+            return Err(RuntimeError::new(super_expr.keyword.clone(), "Can't use 'super' outside of a class."));
+        };
+
+        match superclass.find_method(&super_expr.method.lexeme) {
+            Some(method) => Ok(Value::Callable(Arc::new(method.bind(object)))),
+            None => Err(RuntimeError::new(
+                super_expr.method.clone(),
+                &format!("Undefined property '{}'.", super_expr.method.lexeme)
+            )),
+        }
+    }
+
+    fn visit_this(&self, this: &This) -> Result<Value, RuntimeError> {
+        self.look_up_variable(&this.keyword, this.id)
+    }
+
+    fn visit_unary(&self, unary: &Unary) -> Result<Value, RuntimeError> {
+        let right: Value = self.evaluate(&unary.right)?;
+
+        match unary.operator.type_of {
+            TokenType::Minus => {
+                let n = Self::number_operand(&unary.operator, &right)?;
+                Ok(Value::Float(-n))
+            },
+            TokenType::Bang => Ok(Value::Boolean(!Self::is_truthy(&right))),
+            _ => Err(RuntimeError::new(unary.operator.clone(), "Unknown unary operator.")),
+        }
+    }
+
+    fn visit_variable(&self, variable: &Variable) -> Result<Value, RuntimeError> {
+        self.look_up_variable(&variable.name, variable.id)
+    }
+}
+
+impl StmtVisitor<Result<(), Unwind>> for Interpreter {
+    fn visit_block_stmt(&self, block: &Block) -> Result<(), Unwind> {
+        let scope = Environment::new_enclosed(self.current_environment());
+        self.execute_block(&block.statements, Arc::new(Mutex::new(scope)))
+    }
+
+    fn visit_class_stmt(&self, class: &Class) -> Result<(), Unwind> {
+        let mut superclass: Option<Arc<LoxClass>> = None;
+        if let Some(superclass_expr) = &class.superclass {
+            match self.evaluate(superclass_expr)? {
+                Value::Class(c) => superclass = Some(c),
+                _ => {
+                    let name = match superclass_expr.as_ref() {
+                        Expr::Variable(variable) => variable.name.clone(),
+                        _ => class.name.clone(),
+                    };
+                    return Err(RuntimeError::new(name, "Superclass must be a class.").into());
+                },
+            }
+        }
+
+        let outer = self.current_environment();
+        outer.lock().unwrap().define(&class.name.lexeme, Value::Null);
+
+        // methods of a subclass close over an extra scope that holds `super`
+        let environment = match &superclass {
+            Some(superclass) => {
+                let mut scope = Environment::new_enclosed(outer.clone());
+                scope.define("super", Value::Class(superclass.clone()));
+                Arc::new(Mutex::new(scope))
+            },
+            None => outer.clone(),
+        };
+
+        let mut methods: HashMap<String, Arc<LoxFunction>> = HashMap::new();
+        for method in &class.methods {
+            let is_initializer = method.name.lexeme == "init";
+            let function = LoxFunction::new(method.clone(), environment.clone(), is_initializer);
+            methods.insert(method.name.lexeme.clone(), Arc::new(function));
+        }
+
+        let lox_class = LoxClass::new(&class.name.lexeme, superclass, methods);
+        outer.lock().unwrap().assign(&class.name, Value::Class(Arc::new(lox_class)))?;
+        Ok(())
+    }
+
+    fn visit_expression_stmt(&self, expression: &Expression) -> Result<(), Unwind> {
+        self.evaluate(&expression.expression)?;
+        Ok(())
+    }
+
+    fn visit_function_stmt(&self, function: &Function) -> Result<(), Unwind> {
+        let closure = self.current_environment();
+        let lox_function = LoxFunction::new(function.clone(), closure.clone(), false);
+        closure.lock().unwrap().define(&function.name.lexeme, Value::Callable(Arc::new(lox_function)));
+        Ok(())
+    }
+
+    fn visit_if_stmt(&self, if_stmt: &If) -> Result<(), Unwind> {
+        if Self::is_truthy(&self.evaluate(&if_stmt.condition)?) {
+            self.execute(&if_stmt.then_branch)
+        } else if let Some(else_branch) = &if_stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_print_stmt(&self, print: &Print) -> Result<(), Unwind> {
+        let value: Value = self.evaluate(&print.expression)?;
+        writeln!(self.stdout.lock().unwrap(), "{}", value).expect("Print: could not write output");
+        Ok(())
+    }
+
+    fn visit_return_stmt(&self, return_stmt: &Return) -> Result<(), Unwind> {
+        let value: Value = match &return_stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Null,
+        };
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_var_stmt(&self, var: &Var) -> Result<(), Unwind> {
+        let value: Value = match &var.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Null,
+        };
+        self.current_environment().lock().unwrap().define(&var.name.lexeme, value);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&self, while_stmt: &While) -> Result<(), Unwind> {
+        while Self::is_truthy(&self.evaluate(&while_stmt.condition)?) {
+            self.execute(&while_stmt.body)?;
+        }
+        Ok(())
+    }
+}
+
+/// what cuts a statement short: either an error, or a return carrying its value back to the call
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(token: Token, message: &str) -> Self {
+        RuntimeError{token, message: message.into()}
+    }
+}
@@ -1,6 +1,16 @@
 pub mod ast;
+pub mod callable;
+pub mod class;
+pub mod cst;
+pub mod diagnostics;
+pub mod environment;
+pub mod fmt;
+pub mod interpreter;
+pub mod lower;
 pub mod lox;
+pub mod native;
 pub mod parser;
 pub mod pp;
+pub mod resolver;
 
 
@@ -0,0 +1,202 @@
+use crate::ast::{Assign, Binary, Block, Call, Class, Expr, Expression, Function, Get, Grouping, If, Literal, Logical, Print, Return, Set, Stmt, Super, This, Unary, Value, Var, Variable, While};
+use crate::cst::{SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::lox::{number_value, Token, TokenType};
+
+/// turns a cst into the ast the interpreter runs, for loops desugared and all. Error nodes are left out,
+/// nothing in a declaration that didn't parse is run
+pub struct Lowerer {
+    root:        SyntaxNode,
+    source:      String,
+    /// where the root's spans start, which isn't 0 for source that came after some other source
+    offset:      usize,
+    /// byte offset into `source` each line starts at, to give tokens their line and column back
+    line_starts: Vec<usize>,
+}
+
+impl Lowerer {
+
+    pub fn new(root: &SyntaxNode) -> Self {
+        let source = root.text();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(at, _)| at + 1))
+            .collect();
+        Lowerer {root: root.clone(), source, offset: root.span().start, line_starts}
+    }
+
+    pub fn program(&self) -> Vec<Stmt> {
+        self.declarations(&self.root)
+    }
+
+    /// the statements directly under a Program or Block, skipping the ones that didn't parse
+    fn declarations(&self, node: &SyntaxNode) -> Vec<Stmt> {
+        node.child_nodes().iter().filter_map(|child| self.stmt(child)).collect()
+    }
+
+    fn stmt(&self, node: &SyntaxNode) -> Option<Stmt> {
+        let tokens = node.child_tokens();
+        let nodes = node.child_nodes();
+        match node.kind() {
+            SyntaxKind::VarDecl => {
+                let initializer = match nodes.first() {
+                    Some(expr) => Some(Box::new(self.expr(expr)?)),
+                    None => None,
+                };
+                Some(Var::new(self.token(tokens.get(1)?), initializer))
+            },
+            SyntaxKind::FunDecl => Some(Stmt::Function(self.function(nodes.first()?)?)),
+            SyntaxKind::ClassDecl => {
+                let superclass = match tokens.iter().position(|token| token.kind() == TokenType::Less) {
+                    Some(less) => Some(Box::new(Variable::new(self.token(tokens.get(less + 1)?)))),
+                    None => None,
+                };
+                let methods = nodes.iter().map(|method| self.function(method)).collect::<Option<Vec<_>>>()?;
+                Some(Class::new(self.token(tokens.get(1)?), superclass, methods))
+            },
+            SyntaxKind::ExprStmt => Some(Expression::new(Box::new(self.expr(nodes.first()?)?))),
+            SyntaxKind::PrintStmt => Some(Print::new(Box::new(self.expr(nodes.first()?)?))),
+            SyntaxKind::ReturnStmt => {
+                let value = match nodes.first() {
+                    Some(expr) => Some(Box::new(self.expr(expr)?)),
+                    None => None,
+                };
+                Some(Return::new(self.token(tokens.first()?), value))
+            },
+            SyntaxKind::IfStmt => {
+                let else_branch = match nodes.get(2) {
+                    Some(stmt) => Some(Box::new(self.stmt(stmt)?)),
+                    None => None,
+                };
+                Some(If::new(Box::new(self.expr(nodes.first()?)?), Box::new(self.stmt(nodes.get(1)?)?), else_branch))
+            },
+            SyntaxKind::WhileStmt => Some(While::new(Box::new(self.expr(nodes.first()?)?), Box::new(self.stmt(nodes.get(1)?)?))),
+            SyntaxKind::ForStmt => self.for_stmt(node),
+            SyntaxKind::Block => Some(Block::new(self.declarations(node))),
+            _ => None,
+        }
+    }
+
+    /// desugared the way the book does it, into a while in a block, with the initializer in the block and the
+    /// increment after the body
+    fn for_stmt(&self, node: &SyntaxNode) -> Option<Stmt> {
+        // which part of the header the next child belongs to, moved on by the `;`s and the `)`
+        let mut clause = 0;
+        let (mut initializer, mut condition, mut increment, mut body) = (None, None, None, None);
+        let mut condition_end = 0;
+
+        for child in node.children() {
+            match (child.clone().into_node(), child.into_token()) {
+                (Some(node), _) => match clause {
+                    0 => {
+                        initializer = Some(self.stmt(&node)?);
+                        clause = 1;
+                    },
+                    1 => condition = Some(self.expr(&node)?),
+                    2 => increment = Some(self.expr(&node)?),
+                    _ => body = Some(self.stmt(&node)?),
+                },
+                (_, Some(token)) => match token.kind() {
+                    TokenType::Semicolon if clause == 1 => {
+                        condition_end = token.span().start;
+                        clause = 2;
+                    },
+                    TokenType::Semicolon => clause = 1,
+                    TokenType::RightParen => clause = 3,
+                    _ => {},
+                },
+                _ => {},
+            }
+        }
+
+        let condition = condition.unwrap_or_else(|| Literal::new(Value::Boolean(true), condition_end..condition_end));
+        let mut body: Stmt = body?;
+        if let Some(increment) = increment {
+            body = Block::new(vec![body, Expression::new(Box::new(increment))]);
+        }
+        body = While::new(Box::new(condition), Box::new(body));
+        if let Some(initializer) = initializer {
+            body = Block::new(vec![initializer, body]);
+        }
+        Some(body)
+    }
+
+    fn function(&self, node: &SyntaxNode) -> Option<Function> {
+        let nodes = node.child_nodes();
+        let params = nodes.first()?.child_tokens().iter()
+            .filter(|token| token.kind() == TokenType::Identifier)
+            .map(|token| self.token(token))
+            .collect();
+        Some(Function {
+            name: self.token(node.child_tokens().first()?),
+            params,
+            body: self.declarations(nodes.get(1)?),
+        })
+    }
+
+    fn expr(&self, node: &SyntaxNode) -> Option<Expr> {
+        let tokens = node.child_tokens();
+        let nodes = node.child_nodes();
+        match node.kind() {
+            SyntaxKind::AssignExpr => {
+                let value = Box::new(self.expr(nodes.get(1)?)?);
+                match self.expr(nodes.first()?)? {
+                    Expr::Variable(variable) => Some(Assign::new(variable.name, value)),
+                    Expr::Get(get) => Some(Set::new(get.object, get.name, value)),
+                    _ => None,
+                }
+            },
+            SyntaxKind::BinaryExpr => Some(Binary::new(
+                Box::new(self.expr(nodes.first()?)?), self.token(tokens.first()?), Box::new(self.expr(nodes.get(1)?)?),
+            )),
+            SyntaxKind::LogicalExpr => Some(Logical::new(
+                Box::new(self.expr(nodes.first()?)?), self.token(tokens.first()?), Box::new(self.expr(nodes.get(1)?)?),
+            )),
+            SyntaxKind::UnaryExpr => Some(Unary::new(self.token(tokens.first()?), Box::new(self.expr(nodes.first()?)?))),
+            SyntaxKind::CallExpr => {
+                let arguments = nodes.get(1)?;
+                Some(Call::new(
+                    Box::new(self.expr(nodes.first()?)?),
+                    self.token(arguments.child_tokens().last()?),
+                    arguments.child_nodes().iter().map(|argument| self.expr(argument)).collect::<Option<Vec<_>>>()?,
+                ))
+            },
+            SyntaxKind::GetExpr => Some(Get::new(Box::new(self.expr(nodes.first()?)?), self.token(tokens.get(1)?))),
+            SyntaxKind::GroupingExpr => {
+                let span = tokens.first()?.span().start..tokens.last()?.span().end;
+                Some(Grouping::new(Box::new(self.expr(nodes.first()?)?), span))
+            },
+            SyntaxKind::LiteralExpr => {
+                let token = tokens.first()?;
+                let value = match token.kind() {
+                    TokenType::False => Value::Boolean(false),
+                    TokenType::True => Value::Boolean(true),
+                    TokenType::Nil => Value::Null,
+                    _ => self.token(token).literal,
+                };
+                Some(Literal::new(value, token.span()))
+            },
+            SyntaxKind::VariableExpr => Some(Variable::new(self.token(tokens.first()?))),
+            SyntaxKind::ThisExpr => Some(This::new(self.token(tokens.first()?))),
+            SyntaxKind::SuperExpr => Some(Super::new(self.token(tokens.first()?), self.token(tokens.get(2)?))),
+            _ => None,
+        }
+    }
+
+    /// the token the scanner made, with the same line, column and literal
+    fn token(&self, token: &SyntaxToken) -> Token {
+        let span = token.span();
+        let text = token.text();
+        let literal = match token.kind() {
+            TokenType::Number => number_value(text, text.contains('.')),
+            TokenType::String => Value::String(text[1..text.len() - 1].to_string()),
+            _ => Value::String("".to_string()),
+        };
+
+        // the scanner gives a token the line it ends on, which only matters for strings over several lines
+        let (start, end) = (span.start - self.offset, span.end - self.offset);
+        let line = self.line_starts.partition_point(|&line_start| line_start <= end);
+        let line_start = self.line_starts[self.line_starts.partition_point(|&line_start| line_start <= start) - 1];
+        let column = self.source[line_start..start].chars().count() + 1;
+
+        Token::new(token.kind(), text, literal, line as u32, span, column)
+    }
+}
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
 use std::process;
 use std::fs;
+use std::ops::Range;
 use std::sync::atomic::AtomicU32;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -11,50 +12,119 @@ use std::sync::Mutex;
 use std::sync::RwLock;
 
 use crate::ast::Value;
+use crate::diagnostics::{Diagnostic, ErrorFormat, Renderer, SourceMap};
+use crate::fmt;
+use crate::interpreter::{Interpreter, RuntimeError};
 use crate::parser::Parser;
-use crate::pp::PrettyPrinter;
+use crate::resolver::Resolver;
 
+/// somewhere to write program output or diagnostics to, shared so clones of Lox write to the same place
+pub type Output = Arc<Mutex<dyn Write + Send>>;
+
+/// error flags are shared between clones, so a clone reports back to the same run
+#[derive(Clone)]
 pub struct Lox {
-    had_error: AtomicBool,
+    had_error:         Arc<AtomicBool>,
+    had_runtime_error: Arc<AtomicBool>,
+    interpreter:       Arc<Interpreter>,
+    /// compile and runtime errors are written here
+    stderr:            Output,
+    renderer:          Arc<Mutex<Renderer>>,
+    /// everything that's been run, so diagnostics can quote it
+    sources:           Arc<Mutex<SourceMap>>,
+    /// the script's path, diagnostics name the file when there is one
+    path:              Arc<Mutex<Option<String>>>,
 }
 
-impl Clone for Lox {
-    // TODO: idk about this one but it's just to make it run for now
-    fn clone (&self) -> Self {
-        Lox {
-            had_error: AtomicBool::new(self.had_error.load(Ordering::Relaxed))
-        }
+const USAGE: &str = "Usage: rulox [--error-format=rich|classic|json] [script]\n       rulox fmt [--check] [files...]";
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Lox {
 
+    /// errors are coloured when stderr is a terminal
     pub fn new() -> Self {
+        let lox = Self::with_output(Arc::new(Mutex::new(io::stdout())), Arc::new(Mutex::new(io::stderr())));
+        lox.renderer.lock().unwrap().colour = io::stderr().is_terminal();
+        lox
+    }
+
+    /// for running lox as a library, print statements go to stdout and errors to stderr, without colour
+    pub fn with_output(stdout: Output, stderr: Output) -> Self {
         Self {
-            had_error: AtomicBool::new(false)
+            had_error:         Arc::new(AtomicBool::new(false)),
+            had_runtime_error: Arc::new(AtomicBool::new(false)),
+            interpreter:       Arc::new(Interpreter::with_output(stdout)),
+            stderr,
+            renderer:          Arc::new(Mutex::new(Renderer {format: ErrorFormat::Rich, colour: false})),
+            sources:           Arc::new(Mutex::new(SourceMap::default())),
+            path:              Arc::new(Mutex::new(None)),
         }
     }
+
+    pub fn set_error_format(&self, format: ErrorFormat) {
+        self.renderer.lock().unwrap().format = format;
+    }
     
     pub fn main(&self, args: Vec<String>) {
-        if args.len() > 2usize {
-            println!("Usage: jlox [script]");
-            process::exit(64); 
-        } else if args.len() == 2usize {
-            let file_path = args.get(1).expect("Main: Invalid arg provided");
-            self.run_file(file_path.as_str());
-        } else {
-            self.run_prompt();
+        if args.get(1).is_some_and(|command| command == "fmt") {
+            process::exit(fmt::main(&args[2..]));
+        }
+
+        let (flags, paths): (Vec<&String>, Vec<&String>) = args.iter().skip(1).partition(|arg| arg.starts_with("--"));
+        for flag in flags {
+            match flag.strip_prefix("--error-format=").and_then(ErrorFormat::parse) {
+                Some(format) => self.set_error_format(format),
+                None => {
+                    eprintln!("{}", USAGE);
+                    process::exit(64);
+                },
+            }
+        }
+
+        match paths.as_slice() {
+            [] => self.run_prompt(),
+            [path] => self.run_file(path),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(64);
+            },
+        }
     }
-}
     
+    /// lets the host hand its own functions to scripts, e.g. `lox.define_native("clock", 0, clock)`
+    pub fn define_native<F>(&self, name: &str, arity: usize, function: F)
+    where F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static {
+        self.interpreter.define_native(name, arity, function);
+    }
+
     fn run_file(&self, path: &str) {
         let file = Path::new(&path);
         let contents = fs::read_to_string(file).expect("Run.File: Could not read file");
-        self.run(&contents);
+        *self.path.lock().unwrap() = Some(path.to_string());
+
+        let exit_code = self.run_source(&contents);
+        if exit_code != 0 {process::exit(exit_code)};
+    }
+
+    /// runs a whole program and returns the exit code the binary would finish with,
+    /// 65 for compile errors, 70 for runtime errors and 0 otherwise
+    pub fn run_source(&self, source: &str) -> i32 {
+        self.had_error.store(false, Ordering::SeqCst);
+        self.had_runtime_error.store(false, Ordering::SeqCst);
+        self.run(source);
 
         if self.had_error.load(Ordering::SeqCst) {
-            process::exit(65)
-        };
+            65
+        } else if self.had_runtime_error.load(Ordering::SeqCst) {
+            70
+        } else {
+            0
+        }
     }
 
     fn run_prompt(&self) {
@@ -70,53 +140,135 @@ impl Lox {
         }
     }
     
-    fn run(&self, source: &String) {
-        let scanner: Scanner = Scanner::new(source, self);
-        let tokens: Vec<Token> = scanner.scan_tokens();
+    fn run(&self, source: &str) {
+        // spans carry on from the last run's, functions and classes from earlier runs are still around
+        let offset = self.sources.lock().unwrap().add(source);
+
+        let scanner: Scanner = Scanner::lossless(source).starting_at(offset);
+        let (tokens, diagnostics) = scanner.scan_tokens();
+        for diagnostic in diagnostics {
+            self.report(diagnostic.to_diagnostic());
+        }
 
         let parser: Parser = Parser::new(
             // Arc::new(Mutex::new(self.clone())), 
             tokens
         ); 
-        let pp: PrettyPrinter = PrettyPrinter::new();
-        match parser.parse() {
-            Ok(v) => {println!("{}", pp.print(v));},
-            Err(e) => {self.token_error(e.token, &e.error);}, 
+        let (statements, errors) = parser.parse();
+        for e in errors {
+            self.token_error(e.token, &e.error);
         }
-    }
+        if self.had_error.load(Ordering::SeqCst) {return};
+
+        let resolver: Resolver = Resolver::new(&self.interpreter, self);
+        resolver.resolve(&statements);
+        if self.had_error.load(Ordering::SeqCst) {return};
 
-    fn error(&self, line: u32, message: &str) {
-        self.report(line, "", message);
+        if let Err(e) = self.interpreter.interpret(&statements) {
+            self.runtime_error(e);
+        }
     }
 
-    fn report(&self, line: u32, location: &str, message: &str) {
-        println!("[line {}] Error{}: {}", line, location, message);
+    /// compile errors go to stderr, same as runtime errors
+    pub fn report(&self, diagnostic: Diagnostic) {
+        self.write_diagnostic(&diagnostic);
         self.had_error.store(true, Ordering::SeqCst)
     }
 
     pub fn token_error(&self, token: Token, message: &str) {
-        if matches!(token.type_of, TokenType::Eof) {
-            self.report(token.line, " at end", message);
-        } else {
-            self.report(token.line, &format!(" at '{}'", token.lexeme), message);
+        self.report(Diagnostic::at_token(&token, message));
+    }
+
+    /// runtime errors go to stderr with the line of the offending token
+    pub fn runtime_error(&self, error: RuntimeError) {
+        self.write_diagnostic(&Diagnostic::runtime(&error.token, &error.message));
+        self.had_runtime_error.store(true, Ordering::SeqCst)
+    }
+
+    fn write_diagnostic(&self, diagnostic: &Diagnostic) {
+        let path = self.path.lock().unwrap().clone();
+        let sources = self.sources.lock().unwrap();
+        let (source, diagnostic) = sources.locate(diagnostic);
+        let rendered = self.renderer.lock().unwrap().render(path.as_deref(), source, &diagnostic);
+        write!(self.stderr.lock().unwrap(), "{}", rendered).expect("Report: could not write error");
+    }
+
+}
+
+/// what went wrong while scanning, the scanner reports it and keeps going
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanDiagnosticKind {
+    UnexpectedChar,
+    UnterminatedString,
+    /// digits running straight into letters, like `12abc`
+    MalformedNumber,
+}
+
+impl ScanDiagnosticKind {
+    /// the `code` of its diagnostic
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScanDiagnosticKind::UnexpectedChar     => "unexpected-character",
+            ScanDiagnosticKind::UnterminatedString => "unterminated-string",
+            ScanDiagnosticKind::MalformedNumber    => "malformed-number",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ScanDiagnosticKind::UnexpectedChar     => "Unexpected character.",
+            ScanDiagnosticKind::UnterminatedString => "Unterminated string.",
+            ScanDiagnosticKind::MalformedNumber    => "Malformed number.",
         }
     }
+}
 
+/// a problem in the source found by the scanner, left to whoever called it to report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanDiagnostic {
+    pub kind:   ScanDiagnosticKind,
+    pub line:   u32,
+    /// counted in chars from 1, where the span starts
+    pub column: usize,
+    /// byte offsets into the source
+    pub span:   Range<usize>,
 }
 
+impl ScanDiagnostic {
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::at_span(self.line, self.column, self.span.clone(), self.kind.message())
+            .with_code(self.kind.code());
+        match self.kind {
+            ScanDiagnosticKind::UnexpectedChar => diagnostic.with_label("not part of lox"),
+            ScanDiagnosticKind::UnterminatedString => diagnostic
+                .with_label("this string is never closed")
+                .with_help("add a '\"' where the string should end"),
+            ScanDiagnosticKind::MalformedNumber => diagnostic
+                .with_label("digits followed by letters")
+                .with_help("names can't start with a digit"),
+        }
+    }
+}
+
+/// `start` and `current` are byte offsets into the source, always on a char boundary
 pub struct Scanner {
-    lox:        Lox,
-    source:     String,
-    tokens:     Arc<Mutex<Vec<Token>>>,
-    start:      Arc<AtomicUsize>,
-    current:    Arc<AtomicUsize>,
-    line:       Arc<AtomicU32>,
-    keywords:   Arc<RwLock<HashMap<String, TokenType>>>,
+    /// whether whitespace, comments and text that isn't a token are kept as tokens too, for the cst
+    trivia:      bool,
+    /// added to every span, for source that comes after some other source
+    offset:      usize,
+    source:      String,
+    tokens:      Arc<Mutex<Vec<Token>>>,
+    diagnostics: Arc<Mutex<Vec<ScanDiagnostic>>>,
+    start:       Arc<AtomicUsize>,
+    current:     Arc<AtomicUsize>,
+    line:        Arc<AtomicU32>,
+    keywords:    Arc<RwLock<HashMap<String, TokenType>>>,
 }
 
 impl Scanner {
 
-    fn new(source: &String, lox: &Lox) -> Self {
+    pub fn new(source: &str) -> Self {
         let keywords = Arc::new(RwLock::new(HashMap::<String, TokenType>::from([
             ("and".to_string(), TokenType::And),
             ("class".to_string(), TokenType::Class),
@@ -137,104 +289,106 @@ impl Scanner {
         ])));
 
         Self {
-            source:  source.clone(),
-            tokens:  Arc::new(Mutex::new(vec![])),
-            start:   Arc::new(AtomicUsize::new(0)),
-            current: Arc::new(AtomicUsize::new(0)),
-            line:   Arc::new(AtomicU32::new(1)),
-            lox:    lox.clone(),
+            trivia:      false,
+            offset:      0,
+            source:      source.to_string(),
+            tokens:      Arc::new(Mutex::new(vec![])),
+            diagnostics: Arc::new(Mutex::new(vec![])),
+            start:       Arc::new(AtomicUsize::new(0)),
+            current:     Arc::new(AtomicUsize::new(0)),
+            line:        Arc::new(AtomicU32::new(1)),
             keywords
         }
     }
 
-    pub fn scan_tokens(&self) -> Vec<Token> {
-        loop {
+    /// a scanner whose tokens cover every byte of the source, with trivia between the ones the parser wants
+    pub fn lossless(source: &str) -> Self {
+        Self {trivia: true, ..Self::new(source)}
+    }
+
+    /// spans count from `offset` instead of 0, lines and columns still count from the start of the source
+    pub fn starting_at(self, offset: usize) -> Self {
+        Self {offset, ..self}
+    }
+
+    /// scans the whole source, the tokens always end with Eof even when there were diagnostics
+    pub fn scan_tokens(&self) -> (Vec<Token>, Vec<ScanDiagnostic>) {
+        while !self.is_at_end() {
             let c = self.current.clone().load(Ordering::Relaxed);
             self.start.clone().store(c, Ordering::Relaxed);
             self.scan_token();
-            if self.is_at_end() {break}
         }
         let line = self.line.clone().load(Ordering::Relaxed);
+        let end = self.source.len();
         self.tokens.clone().lock().unwrap().push(
-            Token::new(TokenType::Eof, "", Value::String("".to_string()), line)
+            Token::new(TokenType::Eof, "", Value::String("".to_string()), line, self.span(end..end), self.column(end))
         );
-        let vec = self.tokens.as_ref().lock().unwrap().clone();
-        return vec;
+        (self.tokens.lock().unwrap().clone(), self.diagnostics.lock().unwrap().clone())
     }
 
     fn scan_token(&self) {
-        match self.advance() {
-            Ok(c) => {
-                match c {
-                    '(' => { self.add_token(TokenType::LeftParen, None); },
-                    ')' => { self.add_token(TokenType::RightParen, None); },
-                    '{' => { self.add_token(TokenType::LeftBrace, None); },
-                    '}' => { self.add_token(TokenType::RightBrace, None); },
-                    ',' => { self.add_token(TokenType::Comma, None)},
-                    '.' => { self.add_token(TokenType::Dot, None); },
-                    '-' => { self.add_token(TokenType::Minus, None); },
-                    '+' => { self.add_token(TokenType::Plus, None); },
-                    ';' => { self.add_token(TokenType::Semicolon, None); },
-                    '*' => { self.add_token(TokenType::Star, None); },
-                    '!' => { 
-                        if self.match_next('=') {
-                            self.add_token(TokenType::BangEqual, None); 
-                        } else {
-                            self.add_token(TokenType::Bang, None); 
-                        }
-                    },
-                    '=' => {
-                        if self.match_next('=') {
-                            self.add_token(TokenType::EqualEqual, None); 
-                        } else {
-                            self.add_token(TokenType::Equal, None); 
-                        }
-                    },
-                    '<' => {
-                        if self.match_next('=') {
-                            self.add_token(TokenType::LessEqual, None); 
-                        } else {
-                            self.add_token(TokenType::Less, None); 
-                        }
-                    },
-                    '>' => {
-                        if self.match_next('=') {
-                            self.add_token(TokenType::GreaterEqual, None); 
-                        } else {
-                            self.add_token(TokenType::Greater, None); 
-                        }
-                    },
-                    '/' => {
-                        if self.match_next('/') {
-                            loop { 
-                                if self.peek() == Ok('\n') || self.is_at_end() {break}
-                                Self::handle_advance(self.advance(), "Comment");
-                            };
-                        } else {
-                            self.add_token(TokenType::Slash, None); 
-                        }
-                    },
-                    ' '  => {}, 
-                    '\r' => {},
-                    '\t' => {},
-                    '\n' => { 
-                        let new_line: u32 = self.line.clone().load(Ordering::Relaxed) + 1;
-                        self.line.clone().store(new_line, Ordering::Relaxed);  
-                    },
-                    '"' => self.string(),
-
-                    _c   => { 
-                        if self.is_digit(c) {
-                            self.number();
-                        } else if self.is_alpha(c) {
-                            self.identifier();
-                        } else {
-                            self.lox.error(self.line.clone().load(Ordering::Relaxed), "Unexpected character."); 
-                        }
-                    } 
+        let c = self.advance();
+        match c {
+            '(' => { self.add_token(TokenType::LeftParen, None); },
+            ')' => { self.add_token(TokenType::RightParen, None); },
+            '{' => { self.add_token(TokenType::LeftBrace, None); },
+            '}' => { self.add_token(TokenType::RightBrace, None); },
+            ',' => { self.add_token(TokenType::Comma, None)},
+            '.' => { self.add_token(TokenType::Dot, None); },
+            '-' => { self.add_token(TokenType::Minus, None); },
+            '+' => { self.add_token(TokenType::Plus, None); },
+            ';' => { self.add_token(TokenType::Semicolon, None); },
+            '*' => { self.add_token(TokenType::Star, None); },
+            '!' => { 
+                if self.match_next('=') {
+                    self.add_token(TokenType::BangEqual, None); 
+                } else {
+                    self.add_token(TokenType::Bang, None); 
+                }
+            },
+            '=' => {
+                if self.match_next('=') {
+                    self.add_token(TokenType::EqualEqual, None); 
+                } else {
+                    self.add_token(TokenType::Equal, None); 
                 }
             },
-            Err(e) => {println!("{}", e)}
+            '<' => {
+                if self.match_next('=') {
+                    self.add_token(TokenType::LessEqual, None); 
+                } else {
+                    self.add_token(TokenType::Less, None); 
+                }
+            },
+            '>' => {
+                if self.match_next('=') {
+                    self.add_token(TokenType::GreaterEqual, None); 
+                } else {
+                    self.add_token(TokenType::Greater, None); 
+                }
+            },
+            '/' => {
+                if self.match_next('/') {
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                    if self.trivia {self.add_token(TokenType::Comment, None)};
+                } else {
+                    self.add_token(TokenType::Slash, None); 
+                }
+            },
+            ' ' | '\r' | '\t' | '\n' => self.whitespace(c),
+            '"' => self.string(),
+
+            _c   => { 
+                if self.is_digit(c) {
+                    self.number();
+                } else if self.is_alpha(c) {
+                    self.identifier();
+                } else {
+                    self.diagnose(ScanDiagnosticKind::UnexpectedChar);
+                }
+            } 
         }
     }
     
@@ -243,73 +397,102 @@ impl Scanner {
         self.current.clone().load(Ordering::Relaxed) >= self.source.len()
     }
 
-    /// finds the next char and increments current
-    fn advance(&self) -> Result<char, &str> {
+    /// finds the next char and moves current past it, '\0' once there's nothing left
+    fn advance(&self) -> char {
         let c: usize = self.current.clone().load(Ordering::Relaxed);
-        self.current.clone().store(c + 1usize, Ordering::Relaxed);
-
-        match self.source.chars().nth(c) {
-            Some(char) => {Ok(char)},
-            None       => {Err("Could not advance from current character.")},
+        match self.source[c..].chars().next() {
+            Some(char) => {
+                self.current.clone().store(c + char.len_utf8(), Ordering::Relaxed);
+                char
+            },
+            None => '\0',
         }
     } 
 
     /// finds the next char, if it matches expected, increments current and returns true
     fn match_next(&self, expected: char) -> bool {
-        if self.is_at_end() {return false}
-
-        let c: usize = self.current.clone().load(Ordering::Relaxed);
-        let char: char = self.source.chars().nth(c).unwrap();
-
-        if char != expected {return false}
-
-        self.current.clone().store(c + 1usize, Ordering::Relaxed);
+        if self.is_at_end() || self.peek() != expected {return false}
+        self.advance();
         true 
     }
     
-    /// take a lil peek
-    fn peek(&self) -> Result<char, &str> {
-        if self.is_at_end() { return Ok('\0') } else {
-            let c: usize = self.current.clone().load(Ordering::Relaxed);
-            match self.source.chars().nth(c) {
-                Some(char) => { Ok(char) },
-                None => {Err("Could not peek from current character.")},
-            }
+    /// take a lil peek, '\0' at the end
+    fn peek(&self) -> char {
+        let c: usize = self.current.clone().load(Ordering::Relaxed);
+        self.source[c..].chars().next().unwrap_or('\0')
+    }
+
+    /// counts lines, and when keeping trivia takes the rest of the run of whitespace as one token
+    fn whitespace(&self, c: char) {
+        if c == '\n' {self.line.fetch_add(1, Ordering::Relaxed);}
+        if !self.trivia {return};
+
+        while matches!(self.peek(), ' ' | '\r' | '\t' | '\n') {
+            if self.advance() == '\n' {self.line.fetch_add(1, Ordering::Relaxed);}
         }
+        self.add_token(TokenType::Whitespace, None);
+    }
+
+    /// records a diagnostic covering the token scanned so far, which is kept as an Invalid token when
+    /// keeping trivia
+    fn diagnose(&self, kind: ScanDiagnosticKind) {
+        let start: usize = self.start.clone().load(Ordering::Relaxed);
+        let current: usize = self.current.clone().load(Ordering::Relaxed);
+        self.diagnostics.clone().lock().unwrap().push(ScanDiagnostic {
+            kind,
+            line:   self.line.clone().load(Ordering::Relaxed),
+            column: self.column(start),
+            span:   self.span(start..current),
+        });
+        if self.trivia {self.add_token(TokenType::Invalid, None)};
+    }
+
+    fn span(&self, span: Range<usize>) -> Range<usize> {
+        span.start + self.offset..span.end + self.offset
+    }
+
+    /// the column of a byte offset, in chars from the start of its line
+    fn column(&self, offset: usize) -> usize {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        self.source[line_start..offset].chars().count() + 1
     }
 
     fn add_token(&self, type_of: TokenType, literal: Option<Value>) {
         let line: u32  = self.line.clone().load(Ordering::Relaxed);
         let text: &str = &self.source_substring();
+        let start: usize = self.start.clone().load(Ordering::Relaxed);
+        let span = self.span(start..self.current.clone().load(Ordering::Relaxed));
+        let column = self.column(start);
 
         match literal {
             Some(lit) => {
                 self.tokens.clone().lock().unwrap()
-                    .push(Token::new(type_of, text, lit, line))
+                    .push(Token::new(type_of, text, lit, line, span, column))
             },
             None => {
                 self.tokens.clone().lock().unwrap()
-                    .push(Token::new(type_of, text, Value::String("".to_string()), line)) 
+                    .push(Token::new(type_of, text, Value::String("".to_string()), line, span, column)) 
             },
         }
     }
     
     /// finds the value inside a string "", works for multilines
     fn string(&self) {
-        loop { 
-            if self.peek() == Ok('"') || self.is_at_end() {break}
-            if self.peek() == Ok('\n') {
+        while self.peek() != '"' && !self.is_at_end() {
+            // strings can span lines
+            if self.peek() == '\n' {
                 self.line.fetch_add(1, Ordering::Relaxed);
-                break;
             }
-            Self::handle_advance(self.advance(), "String");
+            self.advance();
         }
 
         if self.is_at_end() {
-            self.lox.error(self.line.clone().load(Ordering::Relaxed), "Unterminated string.");
+            self.diagnose(ScanDiagnosticKind::UnterminatedString);
+            return;
         }
 
-        Self::handle_advance(self.advance(), "String.After");
+        // the closing "
+        self.advance();
 
         let current: usize = self.current.clone().load(Ordering::Relaxed) - 1usize;
         let start:   usize = self.start.clone().load(Ordering::Relaxed) + 1usize;
@@ -320,53 +503,45 @@ impl Scanner {
     
     /// pretty self explanatory
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
     /// tokenizing for number values
     fn number(&self) {
         let mut is_double = false;
-        loop {
-            if !self.is_digit(self.peek().unwrap()) {break}
-            Self::handle_advance(self.advance(), "Number");
-
-            if self.peek().unwrap() == '.' &&
-                self.is_digit(self.peek_next().unwrap()) {
-                is_double = true;
-                loop {
-                    if !self.is_digit(self.peek().unwrap()) {break}
-                    Self::handle_advance(self.advance(), "Number.Decimal");
-                }
+        while self.is_digit(self.peek()) {
+            self.advance();
+        }
+
+        // the fractional part needs a digit after the '.', otherwise the '.' is left for a method call
+        if self.peek() == '.' && self.is_digit(self.peek_next()) {
+            is_double = true;
+            self.advance();
+            while self.is_digit(self.peek()) {
+                self.advance();
             }
-           
         }
-        let lit: String = self.source_substring();
 
-        if is_double {
-            let double: f64 = lit.parse::<f64>().unwrap();
-            self.add_token(TokenType::Number, Some(Value::Float(double)));
-        } else {
-            let integer: u32 = lit.parse::<u32>().unwrap(); 
-            self.add_token(TokenType::Number, Some(Value::Integer(integer)));
+        // letters straight after the digits are part of the mistake, not the start of an identifier
+        if self.is_alpha(self.peek()) {
+            while self.is_alpha_numeric(self.peek()) {
+                self.advance();
+            }
+            self.diagnose(ScanDiagnosticKind::MalformedNumber);
+            return;
         }
+        self.add_token(TokenType::Number, Some(number_value(&self.source_substring(), is_double)));
     }
 
     /// what if peek but twice
-    fn peek_next(&self) -> Result<char, &str> {
-        let new: usize = self.current.clone().load(Ordering::Relaxed) + 1usize;
-
-        if new >= self.source.len() { return Ok('\0') } else {
-            return match self.source.chars().nth(new) {
-                Some(char) => { Ok(char) },
-                None => {Err("Could not peek-next from current character.")},
-            }
-        }
+    fn peek_next(&self) -> char {
+        let c: usize = self.current.clone().load(Ordering::Relaxed);
+        self.source[c..].chars().nth(1).unwrap_or('\0')
     }
 
     fn identifier(&self) {
-        loop {
-            if !self.is_alpha_numeric(self.peek().unwrap()) {break}
-            Self::handle_advance(self.advance(), "Identifier");
+        while self.is_alpha_numeric(self.peek()) {
+            self.advance();
         }
         let text: String = self.source_substring();
         match self.keywords.clone().read().unwrap().get(&text) {
@@ -377,20 +552,12 @@ impl Scanner {
     
     /// is it alpha though???
     fn is_alpha(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+        c.is_ascii_alphabetic() || c == '_'
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
         self.is_alpha(c) || self.is_digit(c)
     }
-    
-    /// error handling for advance when the value doesn't matter
-    fn handle_advance(advance: Result<char, &str>, func: &str) {
-        match advance {
-            Ok(_) => {},
-            Err(e) => println!("{}: Error advancing: {}", func, e),
-        }
-    }
 
     fn source_substring(&self) -> String {
         let current: usize = self.current.clone().load(Ordering::Relaxed);
@@ -399,7 +566,20 @@ impl Scanner {
     }
 }
 
-#[derive(Debug, Clone)]
+/// the value of a number literal's text, anything with a fractional part is a float
+pub fn number_value(text: &str, is_double: bool) -> Value {
+    if is_double {
+        Value::Float(text.parse::<f64>().unwrap())
+    } else {
+        // too big for an integer, it's still a number
+        match text.parse::<u32>() {
+            Ok(integer) => Value::Integer(integer),
+            Err(_) => Value::Float(text.parse::<f64>().unwrap()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
 
 LeftParen, RightParen, LeftBrace, RightBrace, Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
@@ -410,7 +590,18 @@ Identifier, String, Number,
 
 And, Class, Else, False, Fun, For, If, Nil, Or, Print, Return, Super, This, True, Var, While,
 
-Eof
+Eof,
+
+// only from a lossless scanner, the parser never sees these
+Whitespace, Comment, Invalid
+}
+
+impl TokenType {
+
+    /// kept in the cst for the source to round-trip, but not part of the grammar
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenType::Whitespace | TokenType::Comment | TokenType::Invalid)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -418,23 +609,32 @@ pub struct Token {
     pub type_of: TokenType,
     pub lexeme: String,
     pub literal: Value,
+    /// where the token ends, which is only different from where it starts for multi-line strings
     pub line: u32,
+    /// byte offsets into the source, Eof gets the empty span at the very end
+    pub span: Range<usize>,
+    /// counted in chars from 1, of the token's first char
+    pub column: usize,
 }
 
 impl Token {
 
-    pub fn new(type_of: TokenType, lexeme: &str, literal: Value, line: u32) -> Token {
+    pub fn new(type_of: TokenType, lexeme: &str, literal: Value, line: u32, span: Range<usize>, column: usize) -> Token {
         Token {
             type_of,
             lexeme: lexeme.to_string(),
             literal,
-            line
+            line,
+            span,
+            column,
         }
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{:?} {:?} {:?}", self.type_of, self.lexeme, self.literal)
-    }
+}
 
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?} {:?} {:?}", self.type_of, self.lexeme, self.literal)
+    }
 }
 
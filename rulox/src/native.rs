@@ -0,0 +1,58 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::Value;
+use crate::callable::LoxCallable;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::lox::Token;
+
+type NativeFn = dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync;
+
+/// a function implemented by the host, the arity is checked before it's ever called
+pub struct NativeFunction {
+    name:     String,
+    arity:    usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+
+    pub fn new<F>(name: &str, arity: usize, function: F) -> Self
+    where F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(&self, _interpreter: &Interpreter, paren: &Token, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        (self.function)(&arguments).map_err(|message| RuntimeError::new(paren.clone(), &message))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeFunction({}/{})", self.name, self.arity)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+/// seconds since the unix epoch, mostly useful for benchmarking scripts
+pub fn clock(_arguments: &[Value]) -> Result<Value, String> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => Ok(Value::Float(elapsed.as_secs_f64())),
+        Err(e) => Err(format!("Clock went backwards: {}", e)),
+    }
+}
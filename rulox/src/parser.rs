@@ -1,209 +1,543 @@
-use std::sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex};
+use std::ops::Range;
+use std::sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex};
 
-use crate::{ast::{Binary, Expr, Grouping, Literal, Unary, Value}, lox::{Lox, Token, TokenType}};
+use crate::ast::Stmt;
+use crate::cst::{GreenBuilder, GreenNode, SyntaxKind, SyntaxNode};
+use crate::lower::Lowerer;
+use crate::lox::{Token, TokenType};
 
+/// builds a cst out of every token from a lossless scanner, so nothing the scanner saw is lost, and
+/// `Lowerer` makes the ast the interpreter runs out of that. Trivia is added to whichever node is open when
+/// the token after it is, so comments and whitespace lead the node they come before
 pub struct Parser {
-    // lox:         Arc<Mutex<Lox>>,
-    pub tokens:  Vec<Token>,
-    pub current: Arc<AtomicUsize>,
+    /// from a lossless scanner, so trivia included
+    tokens:   Vec<Token>,
+    /// the next token that hasn't been added to the tree, which may be trivia
+    current:  Arc<AtomicUsize>,
+    /// the last token the grammar asked for, which is never trivia
+    previous: Mutex<Option<Token>>,
+    builder:  Mutex<GreenBuilder>,
+    errors:   Mutex<Vec<ParseError>>,
+    /// set by an error and cleared by synchronizing, errors in between are caused by the first and dropped
+    panic_mode: AtomicBool,
+    /// how many blocks are open, synchronizing stops in front of a `}` only when there's one for it to close
+    blocks:     AtomicUsize,
 }
 
 impl Parser {
 
-    pub fn new(
-        // lox: Arc<Mutex<Lox>>, 
-        tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<Token>) -> Self {
         Parser {
-            // lox,
             tokens,
-            current: Arc::new(AtomicUsize::new(0usize))
-        } 
+            current:  Arc::new(AtomicUsize::new(0usize)),
+            previous: Mutex::new(None),
+            builder:  Mutex::new(GreenBuilder::default()),
+            errors:   Mutex::new(vec![]),
+            panic_mode: AtomicBool::new(false),
+            blocks:     AtomicUsize::new(0),
+        }
     }
 
-    pub fn parse(&self) -> Result<Expr, ParseError> {
-        self.expression()
+    /// the statements that parsed and the errors in the ones that didn't, so every syntax error gets reported
+    pub fn parse(self) -> (Vec<Stmt>, Vec<ParseError>) {
+        // the tokens' spans start wherever the scanner was told the source does
+        let offset = self.tokens.first().map_or(0, |token| token.span.start);
+        let (green, errors) = self.parse_tree();
+        (Lowerer::new(&SyntaxNode::new_root_at(Arc::new(green), offset)).program(), errors)
     }
 
-    fn expression(&self) -> Result<Expr, ParseError> {
-        self.equality()
+    /// the Program node, with the Eof and any trivia before it as its last children
+    pub fn parse_tree(self) -> (GreenNode, Vec<ParseError>) {
+        self.start_node(SyntaxKind::Program);
+        while !self.is_at_end() {
+            self.declaration();
+        }
+        let mut builder = self.builder.lock().unwrap();
+        for token in &self.tokens[self.current.load(Ordering::Relaxed)..] {
+            builder.token(token.type_of.clone(), &token.lexeme);
+        }
+        builder.finish_node();
+        drop(builder);
+        (self.builder.into_inner().unwrap().finish(), self.errors.into_inner().unwrap())
     }
 
-    fn equality(&self) -> Result<Expr, ParseError> {
-        let mut expr: Expr = match self.comparison() {
-            Ok(v) => {v},
-            Err(e)=> {return Err(e)},
+    /// a lone Block, for reparsing one by itself. None unless that's all there is and nothing in it is wrong
+    pub fn parse_block(self) -> Option<GreenNode> {
+        if !self.check(TokenType::LeftBrace) {return None};
+        self.start_node(SyntaxKind::Block);
+        self.advance();
+        let closed = self.block().is_ok();
+        self.finish_node();
+        if !closed || !self.is_at_end() || !self.errors.lock().unwrap().is_empty() {return None};
+        Some(self.builder.into_inner().unwrap().finish())
+    }
+
+    /// a declaration with an error is skipped over, and everything it added to the tree goes in an Error
+    /// node along with what was skipped
+    fn declaration(&self) {
+        let (depth, checkpoint) = {
+            let builder = self.builder.lock().unwrap();
+            (builder.depth(), builder.checkpoint())
         };
-        loop {
-            if !self.match_types(vec![TokenType::BangEqual, TokenType::EqualEqual]) {break};
-            let operator: Token = self.previous();
-            let right: Expr = match self.comparison() {
-                Ok(v) => {v},
-                Err(e)=> {return Err(e)},
-            };
-            expr = Binary::new(Box::new(expr), operator, Box::new(right));
+        let start = self.current.load(Ordering::Relaxed);
+        // `error` has already recorded whatever went wrong
+        let _ = self.declaration_or_error();
+        if !self.panic_mode.load(Ordering::Relaxed) {return};
+
+        let mut builder = self.builder.lock().unwrap();
+        builder.finish_to(depth);
+        builder.start_node_at(checkpoint, SyntaxKind::Error);
+        drop(builder);
+        // a declaration that went wrong at its very first token would start there again otherwise
+        if self.current.load(Ordering::Relaxed) == start {self.advance();}
+        self.synchronize();
+        self.finish_node();
+    }
+
+    fn declaration_or_error(&self) -> Result<(), ParseError> {
+        if self.check(TokenType::Class) {return self.class_declaration()};
+        if self.check(TokenType::Fun) {
+            self.start_node(SyntaxKind::FunDecl);
+            self.advance();
+            self.function("function")?;
+            self.finish_node();
+            return Ok(());
+        }
+        if self.check(TokenType::Var) {return self.var_declaration()};
+        self.statement()
+    }
+
+    fn var_declaration(&self) -> Result<(), ParseError> {
+        self.start_node(SyntaxKind::VarDecl);
+        self.advance();
+        self.consume(TokenType::Identifier, "Expected variable name.")?;
+        if self.match_type(TokenType::Equal) {
+            self.expression()?;
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after variable declaration.")?;
+        self.finish_node();
+        Ok(())
+    }
+
+    fn class_declaration(&self) -> Result<(), ParseError> {
+        self.start_node(SyntaxKind::ClassDecl);
+        self.advance();
+        self.consume(TokenType::Identifier, "Expected class name.")?;
+        if self.match_type(TokenType::Less) {
+            self.consume(TokenType::Identifier, "Expected superclass name.")?;
+        }
+
+        self.consume(TokenType::LeftBrace, "Expected '{' before class body.")?;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            self.function("method")?;
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after class body.")?;
+        self.finish_node();
+        Ok(())
+    }
+
+    /// kind is only used to word the error messages, e.g. "function" or "method"
+    fn function(&self, kind: &str) -> Result<(), ParseError> {
+        self.start_node(SyntaxKind::Function);
+        self.consume(TokenType::Identifier, &format!("Expected {} name.", kind))?;
+
+        self.start_node(SyntaxKind::ParamList);
+        self.consume(TokenType::LeftParen, &format!("Expected '(' after {} name.", kind))?;
+        let mut params: usize = 0;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params >= 255 {
+                    return Err(self.error(self.peek(), "Can't have more than 255 parameters."));
+                }
+                self.consume(TokenType::Identifier, "Expected parameter name.")?;
+                params += 1;
+                if !self.match_type(TokenType::Comma) {break};
+            }
         }
+        self.consume(TokenType::RightParen, "Expected ')' after parameters.")?;
+        self.finish_node();
 
-        return Ok(expr); 
+        self.start_node(SyntaxKind::Block);
+        self.consume(TokenType::LeftBrace, &format!("Expected '{{' before {} body.", kind))?;
+        self.block()?;
+        self.finish_node();
+        self.finish_node();
+        Ok(())
     }
 
-    fn match_types(&self, types: Vec<TokenType>) -> bool {
-        for t in types {
-            if self.check(t) {
+    fn statement(&self) -> Result<(), ParseError> {
+        let kind = match self.peek().type_of {
+            TokenType::For => return self.for_statement(),
+            TokenType::If => return self.if_statement(),
+            TokenType::While => return self.while_statement(),
+            TokenType::Print => SyntaxKind::PrintStmt,
+            TokenType::Return => SyntaxKind::ReturnStmt,
+            TokenType::LeftBrace => SyntaxKind::Block,
+            _ => SyntaxKind::ExprStmt,
+        };
+
+        self.start_node(kind);
+        match kind {
+            SyntaxKind::PrintStmt => {
+                self.advance();
+                self.expression()?;
+                self.consume(TokenType::Semicolon, "Expected ';' after value.")?;
+            },
+            SyntaxKind::ReturnStmt => {
+                self.advance();
+                if !self.check(TokenType::Semicolon) {
+                    self.expression()?;
+                }
+                self.consume(TokenType::Semicolon, "Expected ';' after return value.")?;
+            },
+            SyntaxKind::Block => {
                 self.advance();
-                return true
+                self.block()?;
+            },
+            _ => {
+                self.expression()?;
+                self.consume(TokenType::Semicolon, "Expected ';' after expression.")?;
+            },
+        }
+        self.finish_node();
+        Ok(())
+    }
+
+    fn for_statement(&self) -> Result<(), ParseError> {
+        self.start_node(SyntaxKind::ForStmt);
+        self.advance();
+        // like clox, a missing '(' is reported and the clauses parsed anyway, so their `;`s aren't taken for
+        // the ends of statements with errors of their own
+        if !self.match_type(TokenType::LeftParen) {
+            self.error(self.peek(), "Expected '(' after 'for'.");
+        }
+
+        if self.check(TokenType::Var) {
+            self.var_declaration()?;
+        } else if !self.match_type(TokenType::Semicolon) {
+            self.start_node(SyntaxKind::ExprStmt);
+            self.expression()?;
+            self.consume(TokenType::Semicolon, "Expected ';' after expression.")?;
+            self.finish_node();
+        }
+
+        if !self.check(TokenType::Semicolon) {
+            self.expression()?;
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after loop condition.")?;
+
+        if !self.check(TokenType::RightParen) {
+            self.expression()?;
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after for clauses.")?;
+
+        self.statement()?;
+        self.finish_node();
+        Ok(())
+    }
+
+    fn if_statement(&self) -> Result<(), ParseError> {
+        self.start_node(SyntaxKind::IfStmt);
+        self.advance();
+        self.consume(TokenType::LeftParen, "Expected '(' after 'if'.")?;
+        self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after if condition.")?;
+
+        self.statement()?;
+        if self.match_type(TokenType::Else) {
+            self.statement()?;
+        }
+        self.finish_node();
+        Ok(())
+    }
+
+    fn while_statement(&self) -> Result<(), ParseError> {
+        self.start_node(SyntaxKind::WhileStmt);
+        self.advance();
+        self.consume(TokenType::LeftParen, "Expected '(' after 'while'.")?;
+        self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after condition.")?;
+        self.statement()?;
+        self.finish_node();
+        Ok(())
+    }
+
+    /// the rest of a block once its '{' has been added, up to and including the '}'
+    fn block(&self) -> Result<(), ParseError> {
+        self.blocks.fetch_add(1, Ordering::Relaxed);
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            self.declaration();
+        }
+        self.blocks.fetch_sub(1, Ordering::Relaxed);
+        self.consume(TokenType::RightBrace, "Expected '}' after block.")?;
+        Ok(())
+    }
+
+    // every expression rule returns the kind of node it made, which is all assignment needs to check its target
+
+    fn expression(&self) -> Result<SyntaxKind, ParseError> {
+        self.assignment()
+    }
+
+    fn assignment(&self) -> Result<SyntaxKind, ParseError> {
+        let checkpoint = self.checkpoint();
+        let target: SyntaxKind = self.or()?;
+
+        if self.check(TokenType::Equal) {
+            self.start_node_at(checkpoint, SyntaxKind::AssignExpr);
+            let equals: Token = self.advance();
+            self.assignment()?;
+            self.finish_node();
+
+            if !matches!(target, SyntaxKind::VariableExpr | SyntaxKind::GetExpr) {
+                return Err(self.error(equals, "Invalid assignment target."));
             }
+            return Ok(SyntaxKind::AssignExpr);
         }
-        false
+        Ok(target)
     }
 
-    fn check(&self, token_type: TokenType) -> bool {
-        if self.is_at_end() {return false};
-        let p = self.peek();
-        matches!(p.type_of, token_type)
+    fn or(&self) -> Result<SyntaxKind, ParseError> {
+        self.infix(SyntaxKind::LogicalExpr, &[TokenType::Or], Self::and)
     }
-    
-    fn advance(&self) -> Token {
-        if !self.is_at_end() {self.current.fetch_add(1usize, Ordering::Relaxed);};
-        self.previous()
+
+    fn and(&self) -> Result<SyntaxKind, ParseError> {
+        self.infix(SyntaxKind::LogicalExpr, &[TokenType::And], Self::equality)
     }
 
-    fn is_at_end(&self) -> bool {
-        matches!(self.peek().type_of, TokenType::Eof) 
+    fn equality(&self) -> Result<SyntaxKind, ParseError> {
+        self.infix(SyntaxKind::BinaryExpr, &[TokenType::BangEqual, TokenType::EqualEqual], Self::comparison)
     }
 
-    fn peek(&self) -> Token {
-        match self.tokens.get(self.current.clone().load(Ordering::Relaxed)) {
-            Some(t) => {t.clone()},
-            None => panic!("Could not peek into current token"),
+    fn comparison(&self) -> Result<SyntaxKind, ParseError> {
+        let operators = [TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual];
+        self.infix(SyntaxKind::BinaryExpr, &operators, Self::term)
+    }
+
+    fn term(&self) -> Result<SyntaxKind, ParseError> {
+        self.infix(SyntaxKind::BinaryExpr, &[TokenType::Minus, TokenType::Plus], Self::factor)
+    }
+
+    fn factor(&self) -> Result<SyntaxKind, ParseError> {
+        self.infix(SyntaxKind::BinaryExpr, &[TokenType::Slash, TokenType::Star], Self::unary)
+    }
+
+    /// a left associative run of `operand`s separated by any of the operators
+    fn infix(
+        &self,
+        kind: SyntaxKind,
+        operators: &[TokenType],
+        operand: fn(&Self) -> Result<SyntaxKind, ParseError>,
+    ) -> Result<SyntaxKind, ParseError> {
+        let checkpoint = self.checkpoint();
+        let mut expr: SyntaxKind = operand(self)?;
+        while operators.contains(&self.peek().type_of) {
+            self.start_node_at(checkpoint, kind);
+            self.advance();
+            operand(self)?;
+            self.finish_node();
+            expr = kind;
         }
+        Ok(expr)
     }
 
-    fn previous(&self) -> Token {
-        match self.tokens.get(self.current.clone().load(Ordering::Relaxed) - 1usize) {
-            Some(t) => {t.clone()},
-            None => panic!("Could not peek into previous token"),
+    fn unary(&self) -> Result<SyntaxKind, ParseError> {
+        if self.check(TokenType::Bang) || self.check(TokenType::Minus) {
+            self.start_node(SyntaxKind::UnaryExpr);
+            self.advance();
+            self.unary()?;
+            self.finish_node();
+            return Ok(SyntaxKind::UnaryExpr);
         }
+        self.call()
     }
-    
-    fn comparison(&self) -> Result<Expr, ParseError> {
-        let mut expr: Expr = match self.term() {
-            Ok(v) => {v},
-            Err(e)=> {return Err(e)},
-        };
 
+    fn call(&self) -> Result<SyntaxKind, ParseError> {
+        let checkpoint = self.checkpoint();
+        let mut expr: SyntaxKind = self.primary()?;
         loop {
-            if !self.match_types(vec![TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual]) {return Ok(expr)};
-            let operator: Token = self.previous();
-            let right: Expr = match self.term() {
-                Ok(v) => {v},
-                Err(e)=> {return Err(e)},
-            };
-            expr = Binary::new(Box::new(expr), operator, Box::new(right));
-        }
-    }
-    
-    fn term(&self) -> Result<Expr, ParseError>  {
-        let mut expr: Expr = match self.factor() {
-            Ok(v) => {v},
-            Err(e)=> {return Err(e)},
-        };
-        loop {
-            if !self.match_types(vec![TokenType::Minus, TokenType::Plus]) {return Ok(expr)};
-            let operator: Token = self.previous();
-            let right: Expr = match self.factor() {
-                Ok(v) => {v},
-                Err(e)=> {return Err(e)},
-            };
-            expr = Binary::new(Box::new(expr), operator, Box::new(right));
+            if self.check(TokenType::LeftParen) {
+                self.start_node_at(checkpoint, SyntaxKind::CallExpr);
+                self.arguments()?;
+                self.finish_node();
+                expr = SyntaxKind::CallExpr;
+            } else if self.check(TokenType::Dot) {
+                self.start_node_at(checkpoint, SyntaxKind::GetExpr);
+                self.advance();
+                self.consume(TokenType::Identifier, "Expected property name after '.'.")?;
+                self.finish_node();
+                expr = SyntaxKind::GetExpr;
+            } else {
+                break;
+            }
         }
+        Ok(expr)
     }
 
-    fn factor(&self) -> Result<Expr, ParseError>  {
-        let mut expr: Expr = match self.unary() {
-            Ok(v) => {v},
-            Err(e)=> {return Err(e)},
-        };
-        loop {
-            if !self.match_types(vec![TokenType::Slash, TokenType::Star]) {return Ok(expr)};
-            let operator: Token = self.previous();
-            let right: Expr = match self.unary() {
-                Ok(v) => {v},
-                Err(e)=> {return Err(e)},
-            };
-            expr = Binary::new(Box::new(expr), operator, Box::new(right));
-        } 
-    }
-
-    fn unary(&self) -> Result<Expr, ParseError> {
-        if self.match_types(vec![]) {
-            let operator: Token = self.previous();
-            let right: Expr = match self.unary() {
-                Ok(v) => {v},
-                Err(e)=> {return Err(e)},
-            };
-            return Ok(Unary::new(operator, Box::new(right)));
-        }
-        self.primary()
-    }
-
-    fn primary(&self) -> Result<Expr, ParseError> {
-        if self.match_types(vec![TokenType::False]) {return Ok(Literal::new(Value::Boolean(false)))};
-        if self.match_types(vec![TokenType::True])  {return Ok(Literal::new(Value::Boolean(true)))};
-        if self.match_types(vec![TokenType::Nil]) {return Ok(Literal::new(Value::Null))};
-        if self.match_types(vec![TokenType::Number, TokenType::String]) {
-            return Ok(Literal::new(self.previous().literal))
-        }
-        if self.match_types(vec![TokenType::LeftParen]) {
-            let expr = self.expression();
-            match self.consume(TokenType::RightParen, "Expected ')' after expression."){
-                Ok(_) => {},
-                Err(e) => {return Err(e)}
-            };
-            match expr {
-                Ok(e) => return Ok(Grouping::new(Box::new(e))),
-                Err(e) => return Err(e)
+    fn arguments(&self) -> Result<(), ParseError> {
+        self.start_node(SyntaxKind::ArgList);
+        self.advance();
+        let mut arguments: usize = 0;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments >= 255 {
+                    return Err(self.error(self.peek(), "Can't have more than 255 arguments."));
+                }
+                self.expression()?;
+                arguments += 1;
+                if !self.match_type(TokenType::Comma) {break};
             }
         }
-        Err(ParseError{ token: self.peek(), error: "Expected expression.".to_string()})
+        self.consume(TokenType::RightParen, "Expected ')' after arguments.")?;
+        self.finish_node();
+        Ok(())
+    }
+
+    fn primary(&self) -> Result<SyntaxKind, ParseError> {
+        let kind = match self.peek().type_of {
+            TokenType::False | TokenType::True | TokenType::Nil | TokenType::Number | TokenType::String => SyntaxKind::LiteralExpr,
+            TokenType::Super => SyntaxKind::SuperExpr,
+            TokenType::This => SyntaxKind::ThisExpr,
+            TokenType::Identifier => SyntaxKind::VariableExpr,
+            TokenType::LeftParen => SyntaxKind::GroupingExpr,
+            _ => {
+                // like clox, the token that can't start an expression is skipped, synchronizing starts after it.
+                // Not a `}` that closes a block though, that's left for the block
+                let error = self.error(self.peek(), "Expected expression.");
+                let closes_block = self.check(TokenType::RightBrace) && self.blocks.load(Ordering::Relaxed) > 0;
+                if !closes_block {self.advance();}
+                return Err(error);
+            },
+        };
+
+        self.start_node(kind);
+        self.advance();
+        match kind {
+            SyntaxKind::SuperExpr => {
+                self.consume(TokenType::Dot, "Expected '.' after 'super'.")?;
+                self.consume(TokenType::Identifier, "Expected superclass method name.")?;
+            },
+            SyntaxKind::GroupingExpr => {
+                self.expression()?;
+                self.consume(TokenType::RightParen, "Expected ')' after expression.")?;
+            },
+            _ => {},
+        }
+        self.finish_node();
+        Ok(kind)
+    }
+
+    fn start_node(&self, kind: SyntaxKind) {
+        self.builder.lock().unwrap().start_node(kind);
+    }
+
+    fn start_node_at(&self, checkpoint: usize, kind: SyntaxKind) {
+        self.builder.lock().unwrap().start_node_at(checkpoint, kind);
+    }
+
+    fn checkpoint(&self) -> usize {
+        self.builder.lock().unwrap().checkpoint()
+    }
+
+    fn finish_node(&self) {
+        self.builder.lock().unwrap().finish_node();
     }
 
     fn consume(&self, token_type: TokenType, err: &str) -> Result<Token, ParseError> {
         if self.check(token_type) {
             return Ok(self.advance())
         }
-        Err(self.error(self.peek(), err)) 
+        Err(self.error(self.peek(), err))
     }
-    
+
+    /// recorded unless there's already been an error since the last synchronize
     fn error(&self, token: Token, message: &str) -> ParseError {
-        // self.lox.lock().unwrap().token_error(token.clone(), message);
-        ParseError{token, error: message.into()}
+        let error = ParseError{token, error: message.into()};
+        if !self.panic_mode.swap(true, Ordering::Relaxed) {
+            self.errors.lock().unwrap().push(error.clone());
+        }
+        error
     }
 
-    fn synchronize(&self) {
-        self.advance();
-        
-        loop {
-            if self.is_at_end() { break;}
-            let t = self.peek().type_of;
-            match t {
-                TokenType::Class  => {},
-                TokenType::Fun    => {},
-                TokenType::Var    => {},
-                TokenType::For    => {},
-                TokenType::If     => {},
-                TokenType::While  => {},
-                TokenType::Print  => {},
-                TokenType::Return => break,
-                _ => {}
+    fn match_type(&self, token_type: TokenType) -> bool {
+        if self.check(token_type) {
+            self.advance();
+            return true
+        }
+        false
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        self.peek().type_of == token_type
+    }
+
+    /// adds the next token the grammar cares about to the tree, along with the trivia in front of it.
+    /// At the end it stays put, the Eof is added once parsing is done
+    fn advance(&self) -> Token {
+        if self.is_at_end() {return self.peek()};
+        let mut builder = self.builder.lock().unwrap();
+        let mut index = self.current.load(Ordering::Relaxed);
+        while let Some(token) = self.tokens.get(index) {
+            builder.token(token.type_of.clone(), &token.lexeme);
+            index += 1;
+            if !token.type_of.is_trivia() {
+                *self.previous.lock().unwrap() = Some(token.clone());
+                break;
             }
+        }
+        self.current.store(index, Ordering::Relaxed);
+        self.previous()
+    }
 
+    fn is_at_end(&self) -> bool {
+        matches!(self.peek().type_of, TokenType::Eof)
+    }
+
+    /// the next token that isn't trivia, which is Eof at the end
+    fn peek(&self) -> Token {
+        self.tokens[self.current.load(Ordering::Relaxed)..].iter()
+            .find(|token| !token.type_of.is_trivia())
+            .expect("Could not peek into current token")
+            .clone()
+    }
+
+    fn previous(&self) -> Token {
+        self.previous.lock().unwrap().clone().expect("Could not peek into previous token")
+    }
+
+    /// skips to what looks like the start of the next statement, just after a `;` or before a keyword, and
+    /// stops panicking
+    fn synchronize(&self) {
+        self.panic_mode.store(false, Ordering::Relaxed);
+
+        while !self.is_at_end() {
+            if matches!(self.previous().type_of, TokenType::Semicolon) {return};
+            match self.peek().type_of {
+                TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::For |
+                TokenType::If | TokenType::While | TokenType::Print | TokenType::Return => return,
+                // the end of the block the bad statement was in, anywhere else it's a stray one to skip
+                TokenType::RightBrace if self.blocks.load(Ordering::Relaxed) > 0 => return,
+                _ => {},
+            }
             self.advance();
         }
     }
 }
 
-#[derive(Debug)] 
+/// the token the parser stopped at, which carries where in the source that was
+#[derive(Debug, Clone)] 
 pub struct ParseError {
     pub token: Token,
     pub error: String,
 }
+
+impl ParseError {
+
+    pub fn span(&self) -> Range<usize> {
+        self.token.span.clone()
+    }
+
+    pub fn column(&self) -> usize {
+        self.token.column
+    }
+}
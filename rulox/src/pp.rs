@@ -1,7 +1,13 @@
-use crate::ast::{Binary, Expr, Grouping, Literal, Unary, Visitor};
+use crate::ast::{Assign, Binary, Call, Expr, Get, Grouping, Literal, Logical, Set, Super, This, Unary, Variable, Visitor};
 
 pub struct PrettyPrinter;
 
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PrettyPrinter {
 
     pub fn new() -> Self {
@@ -26,6 +32,13 @@ impl PrettyPrinter {
 }
 
 impl Visitor<String> for PrettyPrinter {
+    fn visit_assign(&self, assign: &Assign) -> String {
+        self.parenthesize(
+            &format!("= {}", assign.name.lexeme),
+            &[*assign.value.clone()]
+        )
+    }
+
     fn visit_binary(&self, binary: &Binary) -> String {
         self.parenthesize(
             &binary.operator.lexeme.to_string(),
@@ -33,6 +46,19 @@ impl Visitor<String> for PrettyPrinter {
         )
     }
 
+    fn visit_call(&self, call: &Call) -> String {
+        let mut exprs = vec![*call.callee.clone()];
+        exprs.extend(call.arguments.iter().cloned());
+        self.parenthesize("call", &exprs)
+    }
+
+    fn visit_get(&self, get: &Get) -> String {
+        self.parenthesize(
+            &format!(". {}", get.name.lexeme),
+            &[*get.object.clone()]
+        )
+    }
+
     fn visit_grouping(&self, grouping: &Grouping) -> String {
         self.parenthesize(
             "group",
@@ -41,7 +67,29 @@ impl Visitor<String> for PrettyPrinter {
     }
 
     fn visit_literal(&self, literal: &Literal) -> String {
-        literal.value.clone().to_string()
+        literal.value.to_string()
+    }
+
+    fn visit_logical(&self, logical: &Logical) -> String {
+        self.parenthesize(
+            &logical.operator.lexeme.to_string(),
+            &[*logical.left.clone(), *logical.right.clone()]
+        )
+    }
+
+    fn visit_set(&self, set: &Set) -> String {
+        self.parenthesize(
+            &format!("= {}", set.name.lexeme),
+            &[*set.object.clone(), *set.value.clone()]
+        )
+    }
+
+    fn visit_super(&self, super_expr: &Super) -> String {
+        format!("(super {})", super_expr.method.lexeme)
+    }
+
+    fn visit_this(&self, _this: &This) -> String {
+        "this".to_string()
     }
 
     fn visit_unary(&self, unary: &Unary) -> String { 
@@ -50,5 +98,8 @@ impl Visitor<String> for PrettyPrinter {
             &[*unary.right.clone()]
         )
     }
-}
 
+    fn visit_variable(&self, variable: &Variable) -> String {
+        variable.name.lexeme.to_string()
+    }
+}
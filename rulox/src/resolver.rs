@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use crate::ast::{
+    Assign, Binary, Block, Call, Class, Expr, Expression, Function, Get, Grouping, If, Literal, Logical,
+    Print, Return, Set, Stmt, StmtVisitor, Super, This, Unary, Var, Variable, Visitor, While
+};
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::Interpreter;
+use crate::lox::{Lox, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// a name in a scope, `defined` once its initializer has finished resolving
+#[derive(Debug, Clone)]
+struct Local {
+    defined:     bool,
+    /// the name where it was declared, for pointing back at it
+    declaration: Range<usize>,
+}
+
+/// static pass that runs between parsing and interpreting, it tells the interpreter
+/// how many scopes away each local lives and reports the errors we can catch early
+pub struct Resolver<'a> {
+    interpreter:      &'a Interpreter,
+    lox:              &'a Lox,
+    scopes:           Mutex<Vec<HashMap<String, Local>>>,
+    current_function: Mutex<FunctionType>,
+    current_class:    Mutex<ClassType>,
+}
+
+impl<'a> Resolver<'a> {
+
+    pub fn new(interpreter: &'a Interpreter, lox: &'a Lox) -> Self {
+        Resolver {
+            interpreter,
+            lox,
+            scopes:           Mutex::new(vec![]),
+            current_function: Mutex::new(FunctionType::None),
+            current_class:    Mutex::new(ClassType::None),
+        }
+    }
+
+    pub fn resolve(&self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&self, stmt: &Stmt) {
+        stmt.accept(self)
+    }
+
+    fn resolve_expr(&self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn resolve_function(&self, function: &Function, function_type: FunctionType) {
+        let enclosing = std::mem::replace(&mut *self.current_function.lock().unwrap(), function_type);
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+
+        *self.current_function.lock().unwrap() = enclosing;
+    }
+
+    fn begin_scope(&self) {
+        self.scopes.lock().unwrap().push(HashMap::new());
+    }
+
+    fn end_scope(&self) {
+        self.scopes.lock().unwrap().pop();
+    }
+
+    /// adds the name to the innermost scope, marked as not ready yet
+    fn declare(&self, name: &Token) {
+        let mut scopes = self.scopes.lock().unwrap();
+        let Some(scope) = scopes.last_mut() else {return};
+
+        if let Some(previous) = scope.get(&name.lexeme) {
+            self.lox.report(
+                Diagnostic::at_token(name, "Already a variable with this name in this scope.")
+                    .with_code("duplicate-variable")
+                    .with_secondary(previous.declaration.clone(), "first declared here")
+                    .with_help("pick a different name, or assign to the one already declared"),
+            );
+        }
+        scope.insert(name.lexeme.clone(), Local {defined: false, declaration: name.span.clone()});
+    }
+
+    fn define(&self, name: &Token) {
+        if let Some(scope) = self.scopes.lock().unwrap().last_mut() {
+            scope.insert(name.lexeme.clone(), Local {defined: true, declaration: name.span.clone()});
+        }
+    }
+
+    /// `this` and `super` are declared by the class rather than by a name in the source
+    fn define_keyword(&self, keyword: &str, class: &Token) {
+        if let Some(scope) = self.scopes.lock().unwrap().last_mut() {
+            scope.insert(keyword.to_string(), Local {defined: true, declaration: class.span.clone()});
+        }
+    }
+
+    /// walks outwards from the innermost scope, globals are left unresolved
+    fn resolve_local(&self, id: usize, name: &Token) {
+        let scopes = self.scopes.lock().unwrap();
+        for (depth, scope) in scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.interpreter.resolve(id, depth);
+                return;
+            }
+        }
+    }
+}
+
+impl Visitor<()> for Resolver<'_> {
+    fn visit_assign(&self, assign: &Assign) {
+        self.resolve_expr(&assign.value);
+        self.resolve_local(assign.id, &assign.name);
+    }
+
+    fn visit_binary(&self, binary: &Binary) {
+        self.resolve_expr(&binary.left);
+        self.resolve_expr(&binary.right);
+    }
+
+    fn visit_call(&self, call: &Call) {
+        self.resolve_expr(&call.callee);
+        for argument in &call.arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn visit_get(&self, get: &Get) {
+        self.resolve_expr(&get.object);
+    }
+
+    fn visit_grouping(&self, grouping: &Grouping) {
+        self.resolve_expr(&grouping.expression);
+    }
+
+    fn visit_literal(&self, _literal: &Literal) {}
+
+    fn visit_logical(&self, logical: &Logical) {
+        self.resolve_expr(&logical.left);
+        self.resolve_expr(&logical.right);
+    }
+
+    fn visit_set(&self, set: &Set) {
+        self.resolve_expr(&set.value);
+        self.resolve_expr(&set.object);
+    }
+
+    fn visit_super(&self, super_expr: &Super) {
+        match *self.current_class.lock().unwrap() {
+            ClassType::None => {
+                self.lox.report(
+                    Diagnostic::at_token(&super_expr.keyword, "Can't use 'super' outside of a class.").with_code("super-outside-class")
+                );
+                return;
+            },
+            ClassType::Class => {
+                self.lox.report(
+                    Diagnostic::at_token(&super_expr.keyword, "Can't use 'super' in a class with no superclass.")
+                        .with_code("super-without-superclass")
+                        .with_help("inherit from a class with `class Name < Superclass`"),
+                );
+                return;
+            },
+            ClassType::Subclass => {},
+        }
+        self.resolve_local(super_expr.id, &super_expr.keyword);
+    }
+
+    fn visit_this(&self, this: &This) {
+        if *self.current_class.lock().unwrap() == ClassType::None {
+            self.lox.report(
+                Diagnostic::at_token(&this.keyword, "Can't use 'this' outside of a class.").with_code("this-outside-class")
+            );
+            return;
+        }
+        self.resolve_local(this.id, &this.keyword);
+    }
+
+    fn visit_unary(&self, unary: &Unary) {
+        self.resolve_expr(&unary.right);
+    }
+
+    fn visit_variable(&self, variable: &Variable) {
+        let declared_not_defined = self.scopes.lock().unwrap()
+            .last()
+            .and_then(|scope| scope.get(&variable.name.lexeme))
+            .filter(|local| !local.defined)
+            .cloned();
+        if let Some(local) = declared_not_defined {
+            self.lox.report(
+                Diagnostic::at_token(&variable.name, "Can't read local variable in its own initializer.")
+                    .with_code("read-in-own-initializer")
+                    .with_secondary(local.declaration, "declared here")
+                    .with_note("the variable doesn't exist until its initializer has finished"),
+            );
+        }
+        self.resolve_local(variable.id, &variable.name);
+    }
+}
+
+impl StmtVisitor<()> for Resolver<'_> {
+    fn visit_block_stmt(&self, block: &Block) {
+        self.begin_scope();
+        self.resolve(&block.statements);
+        self.end_scope();
+    }
+
+    /// methods get an extra scope around them holding `this`, and subclasses one more for `super`
+    fn visit_class_stmt(&self, class: &Class) {
+        let enclosing = std::mem::replace(&mut *self.current_class.lock().unwrap(), ClassType::Class);
+
+        self.declare(&class.name);
+        self.define(&class.name);
+
+        if let Some(superclass) = &class.superclass {
+            if let Expr::Variable(variable) = superclass.as_ref()
+                && variable.name.lexeme == class.name.lexeme {
+                self.lox.report(
+                    Diagnostic::at_token(&variable.name, "A class can't inherit from itself.")
+                        .with_code("inherit-from-self")
+                        .with_secondary(class.name.span.clone(), "the class being declared"),
+                );
+            }
+            *self.current_class.lock().unwrap() = ClassType::Subclass;
+            self.resolve_expr(superclass);
+
+            self.begin_scope();
+            self.define_keyword("super", &class.name);
+        }
+
+        self.begin_scope();
+        self.define_keyword("this", &class.name);
+        for method in &class.methods {
+            let function_type = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+
+        if class.superclass.is_some() {
+            self.end_scope();
+        }
+
+        *self.current_class.lock().unwrap() = enclosing;
+    }
+
+    fn visit_expression_stmt(&self, expression: &Expression) {
+        self.resolve_expr(&expression.expression);
+    }
+
+    /// the name is defined before the body, so functions can call themselves
+    fn visit_function_stmt(&self, function: &Function) {
+        self.declare(&function.name);
+        self.define(&function.name);
+        self.resolve_function(function, FunctionType::Function);
+    }
+
+    fn visit_if_stmt(&self, if_stmt: &If) {
+        self.resolve_expr(&if_stmt.condition);
+        self.resolve_stmt(&if_stmt.then_branch);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            self.resolve_stmt(else_branch);
+        }
+    }
+
+    fn visit_print_stmt(&self, print: &Print) {
+        self.resolve_expr(&print.expression);
+    }
+
+    fn visit_return_stmt(&self, return_stmt: &Return) {
+        if *self.current_function.lock().unwrap() == FunctionType::None {
+            self.lox.report(
+                Diagnostic::at_token(&return_stmt.keyword, "Can't return from top-level code.")
+                    .with_code("top-level-return")
+                    .with_note("return only makes sense inside a function or method"),
+            );
+        }
+        if let Some(value) = &return_stmt.value {
+            if *self.current_function.lock().unwrap() == FunctionType::Initializer {
+                self.lox.report(
+                    Diagnostic::at_token(&return_stmt.keyword, "Can't return a value from an initializer.")
+                        .with_code("return-value-from-initializer")
+                        .with_secondary(value.span(), "this value")
+                        .with_note("init always returns the instance, a bare `return;` is allowed"),
+                );
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_var_stmt(&self, var: &Var) {
+        self.declare(&var.name);
+        if let Some(initializer) = &var.initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(&var.name);
+    }
+
+    fn visit_while_stmt(&self, while_stmt: &While) {
+        self.resolve_expr(&while_stmt.condition);
+        self.resolve_stmt(&while_stmt.body);
+    }
+}
@@ -0,0 +1,8 @@
+#[path = "../../testing/conformance.rs"]
+mod conformance;
+
+#[test]
+fn corpus() {
+    // the corpus is annotated with the book's one-line errors
+    conformance::run_corpus(env!("CARGO_BIN_EXE_rulox"), &["--error-format=classic"]);
+}
@@ -0,0 +1,143 @@
+use std::fs;
+use std::ops::Range;
+use std::sync::Arc;
+
+use rulox::cst::{self, GreenElement, Parse, SyntaxKind};
+use rulox::lox::{Scanner, TokenType};
+
+#[path = "../../testing/conformance.rs"]
+#[allow(dead_code)]
+mod conformance;
+
+/// sources the corpus doesn't have, where trivia and errors turn up in awkward places
+const AWKWARD: &[&str] = &[
+    "",
+    "   \n\t// only a comment",
+    "print 1; // no newline at the end",
+    "// leading\nvar a = 1; // trailing\n\n  // between\nprint a;\n",
+    "var s = \"two\nlines\"; print s;\r\n",
+    "print \"ünïcödé\"; // ✓\n",
+    "print 1 @ 2;\n# print 3;\n",
+    "print 12abc;\nprint 3;",
+    "var a = \"never closed",
+    "for (;;) print 1;\nfor (var i = 0; i < 3;) {}\nfor (i = 0; ; i = i + 1) {}",
+    "class A < B { init(a, b) { this.a = a; super.m(); return; } }",
+    "a.b.c = (1 + 2) * -3 / !4 or nil and false; f(1)(2, 3).g();",
+    "var = 1; fun f( { print (; }\nclass {}\nprint 2 print 3;\nif (a) else b;",
+    "(a) = 1; a + b = 2;\nwhile",
+];
+
+/// what went wrong with the cst of one source, None when nothing did
+fn check(source: &str) -> Option<String> {
+    let parse = cst::parse(source);
+    if parse.root.text() != source {
+        return Some(format!("doesn't round-trip, came back as\n{:?}", parse.root.text()));
+    }
+
+    let (tokens, diagnostics) = Scanner::new(source).scan_tokens();
+    if parse.scan_diagnostics != diagnostics {
+        return Some(format!("scan diagnostics were\n{:?}\nexpected\n{:?}", parse.scan_diagnostics, diagnostics));
+    }
+
+    // the tree has every token the plain scanner finds, in the same places, with trivia in between
+    let significant: Vec<String> = parse.root.tokens().iter()
+        .filter(|token| !token.kind().is_trivia())
+        .map(|token| format!("{:?} {:?} {:?}", token.kind(), token.text(), token.span()))
+        .collect();
+    let scanned: Vec<String> = tokens.iter()
+        .map(|token| format!("{:?} {:?} {:?}", token.type_of, token.lexeme, token.span))
+        .collect();
+    if significant != scanned {
+        return Some(format!("tokens were\n{:?}\nexpected\n{:?}", significant, scanned));
+    }
+
+    parse.lower();
+    None
+}
+
+#[test]
+fn corpus_round_trips() {
+    let mut failures: Vec<String> = conformance::corpus().iter()
+        .filter_map(|path| {
+            let source = fs::read_to_string(path).unwrap();
+            check(&source).map(|failure| format!("--- {}\n{}", path.display(), failure))
+        })
+        .collect();
+    failures.extend(AWKWARD.iter().filter_map(|source| {
+        check(source).map(|failure| format!("--- {:?}\n{}", source, failure))
+    }));
+
+    if !failures.is_empty() {
+        panic!("{} sources failed\n\n{}", failures.len(), failures.join("\n\n"));
+    }
+}
+
+/// for each top level child, whether the two trees share it
+fn shared(old: &Parse, new: &Parse) -> Vec<bool> {
+    old.root.green().children.iter().zip(&new.root.green().children)
+        .map(|pair| match pair {
+            (GreenElement::Node(old), GreenElement::Node(new)) => Arc::ptr_eq(old, new),
+            (GreenElement::Token(old), GreenElement::Token(new)) => Arc::ptr_eq(old, new),
+            _ => false,
+        })
+        .collect()
+}
+
+/// the same edit reparsed and parsed from scratch, which should come out the same
+fn reparse_and_parse(parse: &Parse, range: Range<usize>, text: &str) -> (Parse, Parse) {
+    let mut edited = parse.root.text();
+    edited.replace_range(range.clone(), text);
+    let reparsed = parse.reparse(range, text);
+    assert_eq!(reparsed.root.text(), edited);
+    (reparsed, cst::parse(&edited))
+}
+
+#[test]
+fn reparsing_a_block_matches_parsing_from_scratch() {
+    for path in conformance::corpus() {
+        let source = fs::read_to_string(&path).unwrap();
+        let parse = cst::parse(&source);
+        if !parse.errors.is_empty() || !parse.scan_diagnostics.is_empty() {continue};
+
+        let blocks = parse.root.tokens().into_iter()
+            .filter(|token| token.kind() == TokenType::LeftBrace && token.parent().kind() == SyntaxKind::Block);
+        for brace in blocks {
+            let at = brace.span().end;
+            let (reparsed, parsed) = reparse_and_parse(&parse, at..at, "\n  var edited = 1; print edited;");
+            assert_eq!(
+                format!("{:?}", reparsed.root.green()), format!("{:?}", parsed.root.green()),
+                "{} edited after the '{{' at {}", path.display(), at,
+            );
+            // only the statement with the block in it is new
+            assert_eq!(
+                shared(&parse, &reparsed).iter().filter(|shared| !**shared).count(), 1,
+                "{} edited after the '{{' at {} wasn't reparsed on its own", path.display(), at,
+            );
+        }
+    }
+}
+
+#[test]
+fn reparsing_shares_what_the_edit_missed() {
+    let source = "print 1;\n{ print 2; }\nprint 3;\n";
+    let at = source.find('2').unwrap();
+    let parse = cst::parse(source);
+    let (reparsed, _) = reparse_and_parse(&parse, at..at + 1, "4");
+    assert_eq!(reparsed.root.text(), "print 1;\n{ print 4; }\nprint 3;\n");
+
+    // the statements either side, the trailing newline and the Eof
+    let shared: Vec<bool> = shared(&parse, &reparsed);
+    assert_eq!(shared, [true, false, true, true, true]);
+}
+
+#[test]
+fn reparsing_falls_back_when_the_block_does_not_stand_alone() {
+    let source = "{\n  print 1;\n}\nprint 2;\n";
+    let inside = source.find("print 1").unwrap();
+    for text in ["\"open ", "} {", "// ", "print"] {
+        let (reparsed, parsed) = reparse_and_parse(&cst::parse(source), inside..inside, text);
+        assert_eq!(format!("{:?}", reparsed.root.green()), format!("{:?}", parsed.root.green()), "inserting {:?}", text);
+        assert_eq!(reparsed.scan_diagnostics, parsed.scan_diagnostics);
+        assert_eq!(reparsed.errors.len(), parsed.errors.len());
+    }
+}
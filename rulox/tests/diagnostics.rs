@@ -0,0 +1,52 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use rulox::diagnostics::{Diagnostic, ErrorFormat, Renderer};
+
+/// what the prompt writes to stderr for the lines typed into it, after checking it exited cleanly
+fn prompt(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rulox"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(0), "the prompt exited with\n{}", stderr);
+    stderr
+}
+
+#[test]
+fn prompt_quotes_the_line_a_function_came_from() {
+    let stderr = prompt(&[], "fun f() { return nil + 1; }\nf();\n\n");
+    assert!(stderr.contains("--> line 1, column 22"), "{}", stderr);
+    assert!(stderr.contains("1 | fun f() { return nil + 1; }\n  |                      ^"), "{}", stderr);
+}
+
+#[test]
+fn span_outside_the_source_only_gets_a_heading() {
+    let renderer = Renderer {format: ErrorFormat::Rich, colour: false};
+    let diagnostic = Diagnostic::at_span(3, 5, 40..44, "Something.").with_secondary(50..51, "and here");
+    assert_eq!(renderer.render(Some("a.lox"), "print 1;\n", &diagnostic), "error: Something.\n --> a.lox:3:5\n");
+}
+
+#[test]
+fn json_positions_come_from_the_source_the_span_is_in() {
+    let stderr = prompt(&["--error-format=json"], "fun f() { return nil + 1; }\nf();\n\n");
+    assert!(stderr.contains(r#""line":1,"column":22,"span":{"start":21,"end":22}"#), "{}", stderr);
+}
+
+#[test]
+fn json_has_the_secondary_labels() {
+    let renderer = Renderer {format: ErrorFormat::Json, colour: false};
+    let diagnostic = Diagnostic::at_span(2, 7, 19..20, "Already a variable with this name in this scope.")
+        .with_secondary(6..7, "first declared here");
+    let rendered = renderer.render(None, "{ var a = 1;\n  var a = 2; }\n", &diagnostic);
+    assert!(
+        rendered.contains(r#""labels":[{"message":"first declared here","line":1,"column":7,"span":{"start":6,"end":7}}]"#),
+        "{}", rendered,
+    );
+}
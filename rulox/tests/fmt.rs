@@ -0,0 +1,129 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use rulox::cst;
+use rulox::fmt::format;
+
+#[path = "../../testing/conformance.rs"]
+#[allow(dead_code)]
+mod conformance;
+
+/// the tokens other than whitespace, comments trimmed, which formatting mustn't change
+fn significant(source: &str) -> Vec<String> {
+    cst::parse(source).root.tokens().iter()
+        .filter(|token| token.kind() != rulox::lox::TokenType::Whitespace)
+        .map(|token| format!("{:?} {}", token.kind(), token.text().trim_end()))
+        .collect()
+}
+
+#[test]
+fn corpus_is_idempotent_and_keeps_tokens() {
+    let mut formatted_any = false;
+    let failures: Vec<String> = conformance::corpus().iter()
+        .filter_map(|path| {
+            let source = fs::read_to_string(path).unwrap();
+            let formatted = format(&source).ok()?;
+            formatted_any = true;
+            if significant(&formatted) != significant(&source) {
+                return Some(format!("--- {}\nchanged tokens:\n{}", path.display(), formatted));
+            }
+            match format(&formatted) {
+                Ok(again) if again == formatted => None,
+                again => Some(format!("--- {}\nformatted\n{}\nthen\n{:?}", path.display(), formatted, again)),
+            }
+        })
+        .collect();
+
+    assert!(formatted_any);
+    if !failures.is_empty() {
+        panic!("{} files failed\n\n{}", failures.len(), failures.join("\n\n"));
+    }
+}
+
+#[test]
+fn canonical_style() {
+    let source = "\
+// header
+
+
+var a=1;var b = \"x\" ;   // trailing
+class A<B{init(a){this.a=a;}
+  m(){}
+
+  // doc
+  n(){ return super.m( ) ; }
+}
+if(a>1)print a;else{print-b;}
+for(var i=0;i<3;i=i+1){print i;}
+for(;;){}
+while (!a and b or nil) a = a - 1;
+while (a) // until it's gone
+{ a = a - 1; }
+print someFunctionWithALongName(argumentNumberOne, argumentNumberTwo, nested(three, 3), fourth, fifth);
+";
+    let expected = "\
+// header
+
+var a = 1;
+var b = \"x\"; // trailing
+class A < B {
+  init(a) {
+    this.a = a;
+  }
+  m() {}
+
+  // doc
+  n() {
+    return super.m();
+  }
+}
+if (a > 1) print a; else {
+  print -b;
+}
+for (var i = 0; i < 3; i = i + 1) {
+  print i;
+}
+for (;;) {}
+while (!a and b or nil) a = a - 1;
+while (a) { // until it's gone
+  a = a - 1;
+}
+print someFunctionWithALongName(
+  argumentNumberOne,
+  argumentNumberTwo,
+  nested(three, 3),
+  fourth,
+  fifth
+);
+";
+    assert_eq!(format(source).unwrap(), expected);
+    assert_eq!(format(expected).unwrap(), expected);
+}
+
+#[test]
+fn refuses_what_does_not_parse() {
+    let diagnostics = format("print (1;\n").unwrap_err();
+    assert_eq!(diagnostics[0].message, "Expected ')' after expression.");
+}
+
+#[test]
+fn check_exits_non_zero_when_a_file_would_change() {
+    let dir = env::temp_dir().join(format!("rulox-fmt-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (messy, tidy) = (dir.join("messy.rlx"), dir.join("tidy.rlx"));
+    fs::write(&messy, "print  1 ;\n").unwrap();
+    fs::write(&tidy, "print 1;\n").unwrap();
+
+    let fmt = |args: &[&std::path::Path]| {
+        Command::new(env!("CARGO_BIN_EXE_rulox")).arg("fmt").args(args).output().unwrap().status.code()
+    };
+    assert_eq!(fmt(&[std::path::Path::new("--check"), &tidy]), Some(0));
+    assert_eq!(fmt(&[std::path::Path::new("--check"), &messy, &tidy]), Some(1));
+    assert_eq!(fs::read_to_string(&messy).unwrap(), "print  1 ;\n");
+
+    assert_eq!(fmt(&[&messy]), Some(0));
+    assert_eq!(fs::read_to_string(&messy).unwrap(), "print 1;\n");
+    assert_eq!(fmt(&[std::path::Path::new("--check"), &messy]), Some(0));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,14 @@
+use rulox::ast::Stmt;
+use rulox::lox::Scanner;
+use rulox::parser::Parser;
+
+/// the statements around the ones with errors still come back, along with an error for each bad one
+#[test]
+fn parse_returns_what_it_recovered_and_every_error() {
+    let (tokens, _) = Scanner::lossless("var a = 1;\nprint ;\nprint a;\nvar = 2;\nprint a + 1;\n").scan_tokens();
+    let (statements, errors) = Parser::new(tokens).parse();
+
+    assert!(matches!(statements.as_slice(), [Stmt::Var(_), Stmt::Print(_), Stmt::Print(_)]), "{:?}", statements);
+    let errors: Vec<(u32, &str)> = errors.iter().map(|e| (e.token.line, e.error.as_str())).collect();
+    assert_eq!(errors, [(2, "Expected expression."), (4, "Expected variable name.")]);
+}
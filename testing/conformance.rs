@@ -0,0 +1,141 @@
+// shared by rulox/tests/conformance.rs and unrulox/tests/conformance.rs through #[path], so both
+// interpreters are held to the same corpus. Every .rlx file under testing/ is run through the
+// interpreter's binary and its stdout, stderr and exit code are checked against the annotations
+// in the file's comments:
+//
+//   // expect: <line printed to stdout>
+//   // expect runtime error: <message>     the error happens on this line, exit code 70
+//   // [line N] Error<rest>                a compile error reported as written, exit code 65
+//   // Error<rest>                         same, on the line the comment is on
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+const EXPECT_ERROR_AT_LINE: &str = "// [line ";
+const EXPECT_ERROR: &str = "// Error";
+
+#[derive(Debug, Default)]
+pub struct Expectations {
+    stdout:         Vec<String>,
+    compile_errors: Vec<String>,
+    /// the message and the line it should be reported on
+    runtime_error:  Option<(String, usize)>,
+}
+
+impl Expectations {
+
+    pub fn parse(source: &str) -> Self {
+        let mut expectations = Expectations::default();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            if let Some(at) = text.find(EXPECT) {
+                expectations.stdout.push(text[at + EXPECT.len()..].to_string());
+            } else if let Some(at) = text.find(EXPECT_RUNTIME_ERROR) {
+                expectations.runtime_error = Some((text[at + EXPECT_RUNTIME_ERROR.len()..].to_string(), line));
+            } else if let Some(at) = text.find(EXPECT_ERROR_AT_LINE) {
+                expectations.compile_errors.push(text[at + "// ".len()..].to_string());
+            } else if let Some(at) = text.find(EXPECT_ERROR) {
+                expectations.compile_errors.push(format!("[line {}] {}", line, &text[at + "// ".len()..]));
+            }
+        }
+        expectations
+    }
+
+    fn exit_code(&self) -> i32 {
+        if !self.compile_errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+
+    /// every way the run differs from what the file asked for, empty when it passed
+    fn check(&self, stdout: &str, stderr: &str, exit_code: Option<i32>) -> Vec<String> {
+        let mut failures = vec![];
+
+        let stdout: Vec<&str> = stdout.lines().collect();
+        if stdout != self.stdout {
+            failures.push(format!("stdout was\n{}\nexpected\n{}", stdout.join("\n"), self.stdout.join("\n")));
+        }
+
+        let stderr: Vec<&str> = stderr.lines().filter(|line| !line.is_empty()).collect();
+        if let Some((message, line)) = &self.runtime_error {
+            let trace = format!("[line {}]", line);
+            let matches = stderr.first() == Some(&message.as_str())
+                && stderr.get(1).is_some_and(|second| second.starts_with(&trace));
+            if !matches {
+                failures.push(format!("stderr was\n{}\nexpected\n{}\n{}", stderr.join("\n"), message, trace));
+            }
+        } else if stderr != self.compile_errors {
+            failures.push(format!("stderr was\n{}\nexpected\n{}", stderr.join("\n"), self.compile_errors.join("\n")));
+        }
+
+        if exit_code != Some(self.exit_code()) {
+            failures.push(format!("exit code was {:?}, expected {}", exit_code, self.exit_code()));
+        }
+        failures
+    }
+}
+
+/// the testing/ directory at the workspace root
+pub fn testing_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("testing")
+}
+
+/// every .rlx file under testing/, sorted so failures always come out in the same order
+pub fn corpus() -> Vec<PathBuf> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+        let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("can't read {}: {}", dir.display(), e));
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                walk(&path, files);
+            } else if path.extension().is_some_and(|extension| extension == "rlx") {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut files = vec![];
+    walk(&testing_dir(), &mut files);
+    files.sort();
+    files
+}
+
+/// runs one file through the binary, Err has everything that didn't match
+pub fn check_file(binary: &str, args: &[&str], path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("can't read: {}", e))?;
+    let expectations = Expectations::parse(&source);
+
+    let output = Command::new(binary).args(args).arg(path).output()
+        .map_err(|e| format!("can't run {}: {}", binary, e))?;
+    let failures = expectations.check(
+        &String::from_utf8_lossy(&output.stdout),
+        &String::from_utf8_lossy(&output.stderr),
+        output.status.code(),
+    );
+
+    if failures.is_empty() {Ok(())} else {Err(failures.join("\n\n"))}
+}
+
+/// checks the whole corpus and panics with a report of every file that failed
+pub fn run_corpus(binary: &str, args: &[&str]) {
+    let files = corpus();
+    assert!(!files.is_empty(), "no .rlx files under {}", testing_dir().display());
+
+    let failures: Vec<String> = files.iter()
+        .filter_map(|path| check_file(binary, args, path).err().map(|failure| {
+            format!("--- {}\n{}", path.display(), failure)
+        }))
+        .collect();
+
+    if !failures.is_empty() {
+        panic!("{} of {} files failed\n\n{}", failures.len(), files.len(), failures.join("\n\n"));
+    }
+}
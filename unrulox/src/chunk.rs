@@ -0,0 +1,121 @@
+use crate::value::Value;
+
+/// one byte per instruction, operands (if any) follow inline in the code stream.
+/// Return has to stay the last variant, from_byte relies on it
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Invoke,
+    SuperInvoke,
+    Closure,
+    CloseUpvalue,
+    Class,
+    Inherit,
+    Method,
+    Return,
+}
+
+impl OpCode {
+    /// checked decode, for tools that can't trust the byte they're looking at
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        if byte <= OpCode::Return as u8 {
+            Some(unsafe { Self::from_byte_unchecked(byte) })
+        } else {
+            None
+        }
+    }
+
+    /// only for bytes the compiler wrote as an instruction, anything else is garbage
+    ///
+    /// # Safety
+    /// `byte` has to be the discriminant of one of the variants above
+    pub unsafe fn from_byte_unchecked(byte: u8) -> OpCode {
+        unsafe { std::mem::transmute::<u8, OpCode>(byte) }
+    }
+}
+
+/// how many consecutive bytes of code came from the same source line
+#[derive(Debug, Clone, Copy)]
+struct LineRun {
+    line:  u32,
+    count: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code:      Vec<u8>,
+    pub constants: Vec<Value>,
+    lines:         Vec<LineRun>,
+}
+
+impl Chunk {
+
+    pub fn new() -> Self {
+        Chunk {
+            code:      Vec::new(),
+            constants: Vec::new(),
+            lines:     Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, line: u32) {
+        self.code.push(byte);
+        match self.lines.last_mut() {
+            Some(run) if run.line == line => run.count += 1,
+            _ => self.lines.push(LineRun { line, count: 1 }),
+        }
+    }
+
+    pub fn write_op(&mut self, op: OpCode, line: u32) {
+        self.write(op as u8, line);
+    }
+
+    /// returns the index the value lives at in the constant pool
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// walks the run-length encoded line table up to the byte at offset
+    pub fn get_line(&self, offset: usize) -> u32 {
+        let mut remaining = offset;
+        for run in &self.lines {
+            if remaining < run.count {
+                return run.line;
+            }
+            remaining -= run.count;
+        }
+        self.lines.last().map_or(0, |run| run.line)
+    }
+}
@@ -0,0 +1,977 @@
+use std::io::Write;
+
+use crate::chunk::{Chunk, OpCode};
+use crate::debug;
+use crate::scanner::{Scanner, Token, TokenType};
+use crate::object::{Obj, ObjFunction};
+use crate::value::Value;
+use crate::vm::VM;
+
+const UINT8_COUNT: usize = u8::MAX as usize + 1;
+
+/// lowest to highest, the derive gives us the ordering the pratt loop compares with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    None,
+    Assignment, // =
+    Or,         // or
+    And,        // and
+    Equality,   // == !=
+    Comparison, // < > <= >=
+    Term,       // + -
+    Factor,     // * /
+    Unary,      // ! -
+    Call,       // . ()
+    Primary,
+}
+
+impl Precedence {
+    /// one level tighter, binary operators parse their right operand at this level
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+type ParseFn<'src, 'vm> = fn(&mut Compiler<'src, 'vm>, bool);
+
+struct ParseRule<'src, 'vm> {
+    prefix:     Option<ParseFn<'src, 'vm>>,
+    infix:      Option<ParseFn<'src, 'vm>>,
+    precedence: Precedence,
+}
+
+/// a local lives in the stack slot matching its index, depth is None until its initializer is done
+struct Local<'src> {
+    name:        Token<'src>,
+    depth:       Option<usize>,
+    /// a closure captured it, so leaving its scope has to close the upvalue instead of just popping
+    is_captured: bool,
+}
+
+/// where a closure finds a captured variable, either a local slot or an upvalue of the enclosing function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Upvalue {
+    index:    u8,
+    is_local: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionType {
+    Function,
+    Initializer,
+    Method,
+    Script,
+}
+
+impl FunctionType {
+    /// what error messages call it, same words rulox uses
+    fn kind(self) -> &'static str {
+        match self {
+            FunctionType::Initializer | FunctionType::Method => "method",
+            FunctionType::Function | FunctionType::Script => "function",
+        }
+    }
+}
+
+/// one per class body being compiled, innermost last
+struct ClassState {
+    has_superclass: bool,
+}
+
+/// everything that belongs to one function body, nested declarations push another on top
+struct FunctionState<'src> {
+    /// rooted in vm.compiler_roots until end_compiler hands it out
+    function:      *mut ObjFunction,
+    function_type: FunctionType,
+    locals:        Vec<Local<'src>>,
+    upvalues:      Vec<Upvalue>,
+    scope_depth:   usize,
+}
+
+/// single pass: tokens go in one end and bytecode comes out the other, there's no ast in between
+pub struct Compiler<'src, 'vm> {
+    vm:          &'vm mut VM,
+    scanner:     Scanner<'src>,
+    current:     Token<'src>,
+    previous:    Token<'src>,
+    had_error:   bool,
+    panic_mode:  bool,
+    /// innermost function last
+    states:      Vec<FunctionState<'src>>,
+    classes:     Vec<ClassState>,
+}
+
+/// compiles a whole script into its top level function, None means errors were already reported
+pub fn compile(source: &str, vm: &mut VM) -> Option<*mut ObjFunction> {
+    let mut compiler = Compiler::new(source, vm);
+    compiler.begin_function(FunctionType::Script);
+
+    compiler.advance();
+    while !compiler.match_token(TokenType::Eof) {
+        compiler.declaration();
+    }
+    let (function, _) = compiler.end_compiler();
+
+    if compiler.had_error {None} else {Some(function)}
+}
+
+impl<'src, 'vm> Compiler<'src, 'vm> {
+
+    fn new(source: &'src str, vm: &'vm mut VM) -> Self {
+        let placeholder = Token { type_of: TokenType::Eof, lexeme: "", line: 1 };
+        Compiler {
+            vm,
+            scanner:     Scanner::new(source),
+            current:     placeholder,
+            previous:    placeholder,
+            had_error:   false,
+            panic_mode:  false,
+            states:      Vec::new(),
+            classes:     Vec::new(),
+        }
+    }
+
+    /// starts compiling a new function body, named after the previous token unless it's the script
+    fn begin_function(&mut self, function_type: FunctionType) {
+        let function = self.vm.new_function();
+        self.vm.compiler_roots.push(function);
+        if function_type != FunctionType::Script {
+            let name = self.vm.copy_string(self.previous.lexeme);
+            unsafe { (*function).name = name };
+        }
+
+        let mut locals = Vec::with_capacity(UINT8_COUNT);
+        // slot 0 belongs to the function being run, methods find their receiver there as this,
+        // anywhere else the empty name means user code can't refer to it
+        let name = match function_type {
+            FunctionType::Initializer | FunctionType::Method => Self::synthetic_token(TokenType::This, "this", self.previous.line),
+            FunctionType::Function | FunctionType::Script => Self::synthetic_token(TokenType::Eof, "", self.previous.line),
+        };
+        locals.push(Local { name, depth: Some(0), is_captured: false });
+
+        self.states.push(FunctionState {
+            function,
+            function_type,
+            locals,
+            upvalues: Vec::new(),
+            scope_depth: 0,
+        });
+    }
+
+    /// a token that isn't in the source, for names the compiler declares itself
+    fn synthetic_token(type_of: TokenType, lexeme: &'static str, line: u32) -> Token<'src> {
+        Token { type_of, lexeme, line }
+    }
+
+    fn state(&self) -> &FunctionState<'src> {
+        self.states.last().expect("no function being compiled")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState<'src> {
+        self.states.last_mut().expect("no function being compiled")
+    }
+
+    // token plumbing
+
+    fn advance(&mut self) {
+        self.previous = self.current;
+        loop {
+            self.current = self.scanner.scan_token();
+            if self.current.type_of != TokenType::Error {break};
+            self.error_at_current(self.current.lexeme);
+        }
+    }
+
+    fn consume(&mut self, type_of: TokenType, message: &str) {
+        if self.current.type_of == type_of {
+            self.advance();
+            return;
+        }
+        self.error_at_current(message);
+    }
+
+    fn check(&self, type_of: TokenType) -> bool {
+        self.current.type_of == type_of
+    }
+
+    fn match_token(&mut self, type_of: TokenType) -> bool {
+        if !self.check(type_of) {return false};
+        self.advance();
+        true
+    }
+
+    // error reporting, same shape as rulox's Lox::report
+
+    fn error_at_current(&mut self, message: &str) {
+        self.error_at(self.current, message);
+    }
+
+    fn error(&mut self, message: &str) {
+        self.error_at(self.previous, message);
+    }
+
+    /// once we're panicking every other error is swallowed until we synchronize
+    fn error_at(&mut self, token: Token, message: &str) {
+        if self.panic_mode {return};
+        self.panic_mode = true;
+
+        let location = match token.type_of {
+            TokenType::Eof => " at end".to_string(),
+            TokenType::Error => String::new(),
+            _ => format!(" at '{}'", token.lexeme),
+        };
+        writeln!(self.vm.stderr, "[line {}] Error{}: {}", token.line, location, message).expect("could not write error");
+        self.had_error = true;
+    }
+
+    /// skips to something that looks like the start of a statement
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.current.type_of != TokenType::Eof {
+            if self.previous.type_of == TokenType::Semicolon {return};
+            match self.current.type_of {
+                TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::For |
+                TokenType::If | TokenType::While | TokenType::Print | TokenType::Return => return,
+                // the end of the block the bad statement was in, outside one it's a stray one to skip
+                TokenType::RightBrace if self.state().scope_depth > 0 => return,
+                _ => {},
+            }
+            self.advance();
+        }
+    }
+
+    // bytecode emission
+
+    fn current_chunk(&mut self) -> &mut Chunk {
+        unsafe { &mut (*self.state().function).chunk }
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let line = self.previous.line;
+        self.current_chunk().write(byte, line);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    /// for instructions whose runtime errors belong to an earlier line than the token just consumed
+    fn emit_op_at(&mut self, op: OpCode, line: u32) {
+        self.current_chunk().write_op(op, line);
+    }
+
+    fn emit_op_byte(&mut self, op: OpCode, byte: u8) {
+        self.emit_op(op);
+        self.emit_byte(byte);
+    }
+
+    /// falling off the end of a function returns nil, an initializer always returns its instance
+    fn emit_return(&mut self) {
+        if self.state().function_type == FunctionType::Initializer {
+            self.emit_op_byte(OpCode::GetLocal, 0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    /// a number or string the chunk already has shares its slot, names repeat a lot and only 256 fit
+    fn make_constant(&mut self, value: Value) -> u8 {
+        let chunk = self.current_chunk();
+        let constant = match chunk.constants.iter().position(|constant| same_constant(*constant, value)) {
+            Some(constant) => constant,
+            None => chunk.add_constant(value),
+        };
+        if constant > u8::MAX as usize {
+            self.error("Too many constants in one chunk.");
+            return 0;
+        }
+        constant as u8
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        self.emit_op_byte(OpCode::Constant, constant);
+    }
+
+    /// writes a jump with a placeholder offset, returns where the offset lives for patch_jump
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit_byte(0xff);
+        self.emit_byte(0xff);
+        self.current_chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        // -2 to account for the jump offset itself
+        let jump = self.current_chunk().code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+        }
+        let code = &mut self.current_chunk().code;
+        code[offset] = ((jump >> 8) & 0xff) as u8;
+        code[offset + 1] = (jump & 0xff) as u8;
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_op(OpCode::Loop);
+
+        let offset = self.current_chunk().code.len() - loop_start + 2;
+        if offset > u16::MAX as usize {
+            self.error("Loop body too large.");
+        }
+        self.emit_byte(((offset >> 8) & 0xff) as u8);
+        self.emit_byte((offset & 0xff) as u8);
+    }
+
+    /// finishes the innermost function off and stops rooting it, from here on the caller has to keep it alive
+    fn end_compiler(&mut self) -> (*mut ObjFunction, Vec<Upvalue>) {
+        self.emit_return();
+        let state = self.states.pop().expect("no function being compiled");
+        let function = state.function;
+        unsafe { (*function).upvalue_count = state.upvalues.len() };
+
+        if self.vm.print_code && !self.had_error {
+            let name = Value::obj(function as *mut Obj).to_string();
+            debug::disassemble_chunk(unsafe { &(*function).chunk }, &name);
+        }
+        self.vm.compiler_roots.pop();
+        (function, state.upvalues)
+    }
+
+    // scopes and variables
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    /// pops every local that was declared in the scope we're leaving, captured ones move to the heap
+    fn end_scope(&mut self) {
+        self.state_mut().scope_depth -= 1;
+        let scope_depth = self.state().scope_depth;
+        while let Some(local) = self.state().locals.last() {
+            if local.depth.is_none_or(|d| d <= scope_depth) {break};
+            let op = if local.is_captured {OpCode::CloseUpvalue} else {OpCode::Pop};
+            self.emit_op(op);
+            self.state_mut().locals.pop();
+        }
+    }
+
+    fn identifier_constant(&mut self, name: Token) -> u8 {
+        let string = self.vm.copy_string(name.lexeme);
+        self.make_constant(Value::obj(string as *mut Obj))
+    }
+
+    /// innermost local first, so shadowing works, None means it isn't a local of that function
+    fn resolve_local(&mut self, state: usize, name: Token) -> Option<u8> {
+        let found = self.states[state].locals.iter().enumerate().rev()
+            .find(|(_, local)| local.name.lexeme == name.lexeme)
+            .map(|(slot, local)| (slot, local.depth));
+
+        let (slot, depth) = found?;
+        if depth.is_none() {
+            self.error("Can't read local variable in its own initializer.");
+        }
+        Some(slot as u8)
+    }
+
+    /// looks through the enclosing functions, every one in between gets an upvalue threading it down
+    fn resolve_upvalue(&mut self, state: usize, name: Token) -> Option<u8> {
+        if state == 0 {return None};
+        let enclosing = state - 1;
+
+        if let Some(local) = self.resolve_local(enclosing, name) {
+            self.states[enclosing].locals[local as usize].is_captured = true;
+            return Some(self.add_upvalue(state, local, true));
+        }
+        let upvalue = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(state, upvalue, false))
+    }
+
+    /// the same variable captured twice shares a single upvalue
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool) -> u8 {
+        let upvalue = Upvalue { index, is_local };
+        if let Some(existing) = self.states[state].upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+
+        if self.states[state].upvalues.len() == UINT8_COUNT {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+        self.states[state].upvalues.push(upvalue);
+        (self.states[state].upvalues.len() - 1) as u8
+    }
+
+    fn add_local(&mut self, name: Token<'src>) {
+        if self.state().locals.len() == UINT8_COUNT {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        self.state_mut().locals.push(Local { name, depth: None, is_captured: false });
+    }
+
+    /// globals are late bound, so only locals get declared
+    fn declare_variable(&mut self) {
+        let scope_depth = self.state().scope_depth;
+        if scope_depth == 0 {return};
+
+        let name = self.previous;
+        let duplicate = self.state().locals.iter().rev()
+            .take_while(|local| local.depth.is_none_or(|d| d >= scope_depth))
+            .any(|local| local.name.lexeme == name.lexeme);
+        if duplicate {
+            self.error("Already a variable with this name in this scope.");
+        }
+        self.add_local(name);
+    }
+
+    fn parse_variable(&mut self, message: &str) -> u8 {
+        self.consume(TokenType::Identifier, message);
+
+        self.declare_variable();
+        if self.state().scope_depth > 0 {return 0};
+
+        self.identifier_constant(self.previous)
+    }
+
+    fn mark_initialized(&mut self) {
+        let state = self.state_mut();
+        if state.scope_depth == 0 {return};
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(state.scope_depth);
+        }
+    }
+
+    /// locals are already sitting in their slot, so there's nothing to emit for them
+    fn define_variable(&mut self, global: u8) {
+        if self.state().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        self.emit_op_byte(OpCode::DefineGlobal, global);
+    }
+
+    fn named_variable(&mut self, name: Token, can_assign: bool) {
+        let state = self.states.len() - 1;
+        let (get_op, set_op, arg) = if let Some(slot) = self.resolve_local(state, name) {
+            (OpCode::GetLocal, OpCode::SetLocal, slot)
+        } else if let Some(upvalue) = self.resolve_upvalue(state, name) {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, upvalue)
+        } else {
+            let global = self.identifier_constant(name);
+            (OpCode::GetGlobal, OpCode::SetGlobal, global)
+        };
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit_op_byte(set_op, arg);
+        } else {
+            self.emit_op_byte(get_op, arg);
+        }
+    }
+
+    // declarations and statements
+
+    fn declaration(&mut self) {
+        if self.match_token(TokenType::Class) {
+            self.class_declaration();
+        } else if self.match_token(TokenType::Fun) {
+            self.fun_declaration();
+        } else if self.match_token(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+
+        if self.panic_mode {self.synchronize()};
+    }
+
+    /// the class is left on the stack while its methods are attached, and under a `super` local if it inherits
+    fn class_declaration(&mut self) {
+        self.consume(TokenType::Identifier, "Expected class name.");
+        let class_name = self.previous;
+        let name_constant = self.identifier_constant(class_name);
+        self.declare_variable();
+
+        self.emit_op_byte(OpCode::Class, name_constant);
+        self.define_variable(name_constant);
+
+        self.classes.push(ClassState { has_superclass: false });
+
+        if self.match_token(TokenType::Less) {
+            self.consume(TokenType::Identifier, "Expected superclass name.");
+            self.variable(false);
+            if class_name.lexeme == self.previous.lexeme {
+                self.error("A class can't inherit from itself.");
+            }
+
+            self.begin_scope();
+            self.add_local(Self::synthetic_token(TokenType::Super, "super", self.previous.line));
+            self.define_variable(0);
+
+            self.named_variable(class_name, false);
+            self.emit_op(OpCode::Inherit);
+            if let Some(class) = self.classes.last_mut() {class.has_superclass = true};
+        }
+
+        self.named_variable(class_name, false);
+        self.consume(TokenType::LeftBrace, "Expected '{' before class body.");
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof) {
+            self.method();
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after class body.");
+        self.emit_op(OpCode::Pop);
+
+        if self.classes.pop().is_some_and(|class| class.has_superclass) {
+            self.end_scope();
+        }
+    }
+
+    fn method(&mut self) {
+        self.consume(TokenType::Identifier, "Expected method name.");
+        let constant = self.identifier_constant(self.previous);
+
+        let function_type = if self.previous.lexeme == "init" {FunctionType::Initializer} else {FunctionType::Method};
+        self.function(function_type);
+        self.emit_op_byte(OpCode::Method, constant);
+    }
+
+    /// the name is usable inside the body straight away, so functions can recurse
+    fn fun_declaration(&mut self) {
+        let global = self.parse_variable("Expected function name.");
+        self.mark_initialized();
+        self.function(FunctionType::Function);
+        self.define_variable(global);
+    }
+
+    /// compiles the parameters and body into a new function, then emits the closure that wraps it
+    fn function(&mut self, function_type: FunctionType) {
+        self.begin_function(function_type);
+        self.begin_scope();
+
+        let kind = function_type.kind();
+        self.consume(TokenType::LeftParen, &format!("Expected '(' after {} name.", kind));
+        if !self.check(TokenType::RightParen) {
+            loop {
+                let function = self.state().function;
+                unsafe { (*function).arity += 1 };
+                if unsafe { (*function).arity } > 255 {
+                    self.error_at_current("Can't have more than 255 parameters.");
+                }
+                let constant = self.parse_variable("Expected parameter name.");
+                self.define_variable(constant);
+                if !self.match_token(TokenType::Comma) {break};
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after parameters.");
+        self.consume(TokenType::LeftBrace, &format!("Expected '{{' before {} body.", kind));
+        self.block();
+
+        // no end_scope, the frame's slots go away with the return
+        let (function, upvalues) = self.end_compiler();
+        let constant = self.make_constant(Value::obj(function as *mut Obj));
+        self.emit_op_byte(OpCode::Closure, constant);
+        for upvalue in upvalues {
+            self.emit_byte(if upvalue.is_local {1} else {0});
+            self.emit_byte(upvalue.index);
+        }
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expected variable name.");
+
+        if self.match_token(TokenType::Equal) {
+            self.expression();
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.consume(TokenType::Semicolon, "Expected ';' after variable declaration.");
+
+        self.define_variable(global);
+    }
+
+    fn statement(&mut self) {
+        if self.match_token(TokenType::Print) {
+            self.print_statement();
+        } else if self.match_token(TokenType::For) {
+            self.for_statement();
+        } else if self.match_token(TokenType::If) {
+            self.if_statement();
+        } else if self.match_token(TokenType::Return) {
+            self.return_statement();
+        } else if self.match_token(TokenType::While) {
+            self.while_statement();
+        } else if self.match_token(TokenType::LeftBrace) {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        } else {
+            self.expression_statement();
+        }
+    }
+
+    fn block(&mut self) {
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof) {
+            self.declaration();
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after block.");
+    }
+
+    fn print_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expected ';' after value.");
+        self.emit_op(OpCode::Print);
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expected ';' after expression.");
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn return_statement(&mut self) {
+        if self.state().function_type == FunctionType::Script {
+            self.error("Can't return from top-level code.");
+        }
+
+        if self.match_token(TokenType::Semicolon) {
+            self.emit_return();
+        } else {
+            if self.state().function_type == FunctionType::Initializer {
+                self.error("Can't return a value from an initializer.");
+            }
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expected ';' after return value.");
+            self.emit_op(OpCode::Return);
+        }
+    }
+
+    fn if_statement(&mut self) {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'if'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expected ')' after if condition.");
+
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.statement();
+
+        let else_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+
+        if self.match_token(TokenType::Else) {self.statement()};
+        self.patch_jump(else_jump);
+    }
+
+    fn while_statement(&mut self) {
+        let loop_start = self.current_chunk().code.len();
+        self.consume(TokenType::LeftParen, "Expected '(' after 'while'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expected ')' after condition.");
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+    }
+
+    /// the increment is compiled before the body, so the body jumps back over it and then loops to the condition
+    fn for_statement(&mut self) {
+        self.begin_scope();
+        self.consume(TokenType::LeftParen, "Expected '(' after 'for'.");
+        if self.match_token(TokenType::Semicolon) {
+            // no initializer
+        } else if self.match_token(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.current_chunk().code.len();
+        let mut exit_jump: Option<usize> = None;
+        if !self.match_token(TokenType::Semicolon) {
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expected ';' after loop condition.");
+
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse));
+            self.emit_op(OpCode::Pop);
+        }
+
+        if !self.match_token(TokenType::RightParen) {
+            let body_jump = self.emit_jump(OpCode::Jump);
+            let increment_start = self.current_chunk().code.len();
+            self.expression();
+            self.emit_op(OpCode::Pop);
+            self.consume(TokenType::RightParen, "Expected ')' after for clauses.");
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_op(OpCode::Pop);
+        }
+        self.end_scope();
+    }
+
+    // expressions
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    /// the heart of the pratt parser, keeps folding infix operators while they bind tighter than precedence
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        // a `}` is left for the block it closes, synchronizing stops in front of it
+        if self.check(TokenType::RightBrace) && self.state().scope_depth > 0 {
+            self.error_at_current("Expected expression.");
+            return;
+        }
+        self.advance();
+        let Some(prefix_rule) = Self::get_rule(self.previous.type_of).prefix else {
+            self.error("Expected expression.");
+            return;
+        };
+
+        let can_assign = precedence <= Precedence::Assignment;
+        prefix_rule(self, can_assign);
+
+        while precedence <= Self::get_rule(self.current.type_of).precedence {
+            self.advance();
+            if let Some(infix_rule) = Self::get_rule(self.previous.type_of).infix {
+                infix_rule(self, can_assign);
+            }
+        }
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.error("Invalid assignment target.");
+        }
+    }
+
+    fn get_rule(type_of: TokenType) -> ParseRule<'src, 'vm> {
+        let rule = |prefix: Option<ParseFn<'src, 'vm>>, infix: Option<ParseFn<'src, 'vm>>, precedence| {
+            ParseRule { prefix, infix, precedence }
+        };
+
+        match type_of {
+            TokenType::LeftParen    => rule(Some(Self::grouping), Some(Self::call), Precedence::Call),
+            TokenType::Dot          => rule(None, Some(Self::dot), Precedence::Call),
+            TokenType::Minus        => rule(Some(Self::unary), Some(Self::binary), Precedence::Term),
+            TokenType::Plus         => rule(None, Some(Self::binary), Precedence::Term),
+            TokenType::Slash        => rule(None, Some(Self::binary), Precedence::Factor),
+            TokenType::Star         => rule(None, Some(Self::binary), Precedence::Factor),
+            TokenType::Bang         => rule(Some(Self::unary), None, Precedence::None),
+            TokenType::BangEqual    => rule(None, Some(Self::binary), Precedence::Equality),
+            TokenType::EqualEqual   => rule(None, Some(Self::binary), Precedence::Equality),
+            TokenType::Greater      => rule(None, Some(Self::binary), Precedence::Comparison),
+            TokenType::GreaterEqual => rule(None, Some(Self::binary), Precedence::Comparison),
+            TokenType::Less         => rule(None, Some(Self::binary), Precedence::Comparison),
+            TokenType::LessEqual    => rule(None, Some(Self::binary), Precedence::Comparison),
+            TokenType::Identifier   => rule(Some(Self::variable), None, Precedence::None),
+            TokenType::String       => rule(Some(Self::string), None, Precedence::None),
+            TokenType::Number       => rule(Some(Self::number), None, Precedence::None),
+            TokenType::And          => rule(None, Some(Self::and), Precedence::And),
+            TokenType::Or           => rule(None, Some(Self::or), Precedence::Or),
+            TokenType::False        => rule(Some(Self::literal), None, Precedence::None),
+            TokenType::Nil          => rule(Some(Self::literal), None, Precedence::None),
+            TokenType::Super        => rule(Some(Self::super_), None, Precedence::None),
+            TokenType::This         => rule(Some(Self::this), None, Precedence::None),
+            TokenType::True         => rule(Some(Self::literal), None, Precedence::None),
+            _                       => rule(None, None, Precedence::None),
+        }
+    }
+
+    fn grouping(&mut self, _can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightParen, "Expected ')' after expression.");
+    }
+
+    fn call(&mut self, _can_assign: bool) {
+        let arg_count = self.argument_list();
+        self.emit_op_byte(OpCode::Call, arg_count);
+    }
+
+    /// a property access, or a method call compiled straight to OP_INVOKE
+    fn dot(&mut self, can_assign: bool) {
+        self.consume(TokenType::Identifier, "Expected property name after '.'.");
+        let name = self.identifier_constant(self.previous);
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit_op_byte(OpCode::SetProperty, name);
+        } else if self.match_token(TokenType::LeftParen) {
+            let arg_count = self.argument_list();
+            self.emit_op_byte(OpCode::Invoke, name);
+            self.emit_byte(arg_count);
+        } else {
+            self.emit_op_byte(OpCode::GetProperty, name);
+        }
+    }
+
+    /// this is just a local in slot 0, assigning to it is a parse error like anywhere else
+    fn this(&mut self, _can_assign: bool) {
+        if self.classes.is_empty() {
+            self.error("Can't use 'this' outside of a class.");
+            return;
+        }
+        self.variable(false);
+    }
+
+    /// pushes this, then the superclass from the enclosing `super` local, then looks the method up on it
+    fn super_(&mut self, _can_assign: bool) {
+        match self.classes.last() {
+            None => self.error("Can't use 'super' outside of a class."),
+            Some(class) if !class.has_superclass => self.error("Can't use 'super' in a class with no superclass."),
+            Some(_) => {},
+        }
+
+        self.consume(TokenType::Dot, "Expected '.' after 'super'.");
+        self.consume(TokenType::Identifier, "Expected superclass method name.");
+        let name = self.identifier_constant(self.previous);
+        let line = self.previous.line;
+
+        self.named_variable(Self::synthetic_token(TokenType::This, "this", line), false);
+        if self.match_token(TokenType::LeftParen) {
+            let arg_count = self.argument_list();
+            self.named_variable(Self::synthetic_token(TokenType::Super, "super", line), false);
+            self.emit_op_byte(OpCode::SuperInvoke, name);
+            self.emit_byte(arg_count);
+        } else {
+            self.named_variable(Self::synthetic_token(TokenType::Super, "super", line), false);
+            self.emit_op_byte(OpCode::GetSuper, name);
+        }
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut arg_count: usize = 0;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arg_count == 255 {
+                    self.error_at_current("Can't have more than 255 arguments.");
+                }
+                self.expression();
+                arg_count += 1;
+                if !self.match_token(TokenType::Comma) {break};
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after arguments.");
+        arg_count.min(255) as u8
+    }
+
+    fn number(&mut self, _can_assign: bool) {
+        match self.previous.lexeme.parse::<f64>() {
+            Ok(n) => self.emit_constant(Value::number(n)),
+            Err(_) => self.error("Invalid number literal."),
+        }
+    }
+
+    /// trims the quotes off the lexeme
+    fn string(&mut self, _can_assign: bool) {
+        let lexeme = self.previous.lexeme;
+        let string = self.vm.copy_string(&lexeme[1..lexeme.len() - 1]);
+        self.emit_constant(Value::obj(string as *mut Obj));
+    }
+
+    fn variable(&mut self, can_assign: bool) {
+        self.named_variable(self.previous, can_assign);
+    }
+
+    fn literal(&mut self, _can_assign: bool) {
+        match self.previous.type_of {
+            TokenType::False => self.emit_op(OpCode::False),
+            TokenType::Nil => self.emit_op(OpCode::Nil),
+            TokenType::True => self.emit_op(OpCode::True),
+            _ => {},
+        }
+    }
+
+    fn unary(&mut self, _can_assign: bool) {
+        let operator = self.previous.type_of;
+        let line = self.previous.line;
+        self.parse_precedence(Precedence::Unary);
+
+        match operator {
+            TokenType::Bang => self.emit_op_at(OpCode::Not, line),
+            TokenType::Minus => self.emit_op_at(OpCode::Negate, line),
+            _ => {},
+        }
+    }
+
+    /// a != b compiles to !(a == b), there's no instruction for it
+    fn binary(&mut self, _can_assign: bool) {
+        let operator = self.previous.type_of;
+        // errors point at the operator, not wherever the operand ended
+        let line = self.previous.line;
+        let rule = Self::get_rule(operator);
+        self.parse_precedence(rule.precedence.next());
+
+        match operator {
+            TokenType::BangEqual => {
+                self.emit_op_at(OpCode::Equal, line);
+                self.emit_op_at(OpCode::Not, line);
+            },
+            TokenType::EqualEqual => self.emit_op_at(OpCode::Equal, line),
+            TokenType::Greater => self.emit_op_at(OpCode::Greater, line),
+            // not `!(a < b)`, which would be true when either side is nan
+            TokenType::GreaterEqual => self.emit_op_at(OpCode::GreaterEqual, line),
+            TokenType::Less => self.emit_op_at(OpCode::Less, line),
+            TokenType::LessEqual => self.emit_op_at(OpCode::LessEqual, line),
+            TokenType::Plus => self.emit_op_at(OpCode::Add, line),
+            TokenType::Minus => self.emit_op_at(OpCode::Subtract, line),
+            TokenType::Star => self.emit_op_at(OpCode::Multiply, line),
+            TokenType::Slash => self.emit_op_at(OpCode::Divide, line),
+            _ => {},
+        }
+    }
+
+    /// if the left side is falsey it's the result, and the right side is skipped
+    fn and(&mut self, _can_assign: bool) {
+        let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+
+        self.emit_op(OpCode::Pop);
+        self.parse_precedence(Precedence::And);
+
+        self.patch_jump(end_jump);
+    }
+
+    fn or(&mut self, _can_assign: bool) {
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(else_jump);
+        self.emit_op(OpCode::Pop);
+
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+}
+
+/// numbers compare by their bits so 0 and -0 stay apart, strings are interned so the pointer is enough
+fn same_constant(a: Value, b: Value) -> bool {
+    if a.is_number() && b.is_number() {
+        a.as_number().to_bits() == b.as_number().to_bits()
+    } else {
+        a.is_obj() && b.is_obj() && a.as_obj() == b.as_obj()
+    }
+}
@@ -0,0 +1,130 @@
+use crate::chunk::{Chunk, OpCode};
+use crate::object::{ObjFunction, ObjType};
+
+/// prints every instruction in the chunk under a `== name ==` header
+pub fn disassemble_chunk(chunk: &Chunk, name: &str) {
+    println!("== {} ==", name);
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset);
+    }
+}
+
+/// prints the instruction at offset and returns where the next one starts
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
+    print!("{:04} ", offset);
+    let line = chunk.get_line(offset);
+    if offset > 0 && line == chunk.get_line(offset - 1) {
+        print!("   | ");
+    } else {
+        print!("{:4} ", line);
+    }
+
+    let byte = chunk.code[offset];
+    let Some(instruction) = OpCode::from_byte(byte) else {
+        println!("Unknown opcode {}", byte);
+        return offset + 1;
+    };
+
+    match instruction {
+        OpCode::Constant     => constant_instruction("OP_CONSTANT", chunk, offset),
+        OpCode::Nil          => simple_instruction("OP_NIL", offset),
+        OpCode::True         => simple_instruction("OP_TRUE", offset),
+        OpCode::False        => simple_instruction("OP_FALSE", offset),
+        OpCode::Pop          => simple_instruction("OP_POP", offset),
+        OpCode::GetLocal     => byte_instruction("OP_GET_LOCAL", chunk, offset),
+        OpCode::SetLocal     => byte_instruction("OP_SET_LOCAL", chunk, offset),
+        OpCode::GetGlobal    => constant_instruction("OP_GET_GLOBAL", chunk, offset),
+        OpCode::DefineGlobal => constant_instruction("OP_DEFINE_GLOBAL", chunk, offset),
+        OpCode::SetGlobal    => constant_instruction("OP_SET_GLOBAL", chunk, offset),
+        OpCode::GetUpvalue   => byte_instruction("OP_GET_UPVALUE", chunk, offset),
+        OpCode::SetUpvalue   => byte_instruction("OP_SET_UPVALUE", chunk, offset),
+        OpCode::GetProperty  => constant_instruction("OP_GET_PROPERTY", chunk, offset),
+        OpCode::SetProperty  => constant_instruction("OP_SET_PROPERTY", chunk, offset),
+        OpCode::GetSuper     => constant_instruction("OP_GET_SUPER", chunk, offset),
+        OpCode::Equal        => simple_instruction("OP_EQUAL", offset),
+        OpCode::Greater      => simple_instruction("OP_GREATER", offset),
+        OpCode::GreaterEqual => simple_instruction("OP_GREATER_EQUAL", offset),
+        OpCode::Less         => simple_instruction("OP_LESS", offset),
+        OpCode::LessEqual    => simple_instruction("OP_LESS_EQUAL", offset),
+        OpCode::Add          => simple_instruction("OP_ADD", offset),
+        OpCode::Subtract     => simple_instruction("OP_SUBTRACT", offset),
+        OpCode::Multiply     => simple_instruction("OP_MULTIPLY", offset),
+        OpCode::Divide       => simple_instruction("OP_DIVIDE", offset),
+        OpCode::Not          => simple_instruction("OP_NOT", offset),
+        OpCode::Negate       => simple_instruction("OP_NEGATE", offset),
+        OpCode::Print        => simple_instruction("OP_PRINT", offset),
+        OpCode::Jump         => jump_instruction("OP_JUMP", 1, chunk, offset),
+        OpCode::JumpIfFalse  => jump_instruction("OP_JUMP_IF_FALSE", 1, chunk, offset),
+        OpCode::Loop         => jump_instruction("OP_LOOP", -1, chunk, offset),
+        OpCode::Call         => byte_instruction("OP_CALL", chunk, offset),
+        OpCode::Invoke       => invoke_instruction("OP_INVOKE", chunk, offset),
+        OpCode::SuperInvoke  => invoke_instruction("OP_SUPER_INVOKE", chunk, offset),
+        OpCode::Closure      => closure_instruction(chunk, offset),
+        OpCode::CloseUpvalue => simple_instruction("OP_CLOSE_UPVALUE", offset),
+        OpCode::Class        => constant_instruction("OP_CLASS", chunk, offset),
+        OpCode::Inherit      => simple_instruction("OP_INHERIT", offset),
+        OpCode::Method       => constant_instruction("OP_METHOD", chunk, offset),
+        OpCode::Return       => simple_instruction("OP_RETURN", offset),
+    }
+}
+
+fn simple_instruction(name: &str, offset: usize) -> usize {
+    println!("{}", name);
+    offset + 1
+}
+
+/// operand is a raw byte, a stack slot for the local instructions
+fn byte_instruction(name: &str, chunk: &Chunk, offset: usize) -> usize {
+    let slot = chunk.code[offset + 1];
+    println!("{:<16} {:4}", name, slot);
+    offset + 2
+}
+
+/// operand is an index into the constant pool, the constant itself is printed too
+fn constant_instruction(name: &str, chunk: &Chunk, offset: usize) -> usize {
+    let constant = chunk.code[offset + 1];
+    println!("{:<16} {:4} '{}'", name, constant, chunk.constants[constant as usize]);
+    offset + 2
+}
+
+/// method name constant, then the argument count
+fn invoke_instruction(name: &str, chunk: &Chunk, offset: usize) -> usize {
+    let constant = chunk.code[offset + 1];
+    let arg_count = chunk.code[offset + 2];
+    println!("{:<16} ({} args) {:4} '{}'", name, arg_count, constant, chunk.constants[constant as usize]);
+    offset + 3
+}
+
+/// the function constant is followed by an (is_local, index) byte pair for every upvalue it captures.
+/// A chunk put together by hand can have anything there, and without a function there's no telling how
+/// many pairs follow
+fn closure_instruction(chunk: &Chunk, offset: usize) -> usize {
+    let constant = chunk.code[offset + 1];
+    let value = chunk.constants[constant as usize];
+    if !value.is_obj_type(ObjType::Function) {
+        println!("{:<16} {:4} '{}' is not a function", "OP_CLOSURE", constant, value);
+        return offset + 2;
+    }
+    println!("{:<16} {:4} {}", "OP_CLOSURE", constant, value);
+
+    let function = value.as_obj() as *mut ObjFunction;
+    let upvalue_count = unsafe { (*function).upvalue_count };
+    let mut offset = offset + 2;
+    for _ in 0..upvalue_count {
+        let is_local = chunk.code[offset];
+        let index = chunk.code[offset + 1];
+        println!("{:04}    |                     {} {}", offset, if is_local == 1 {"local"} else {"upvalue"}, index);
+        offset += 2;
+    }
+    offset
+}
+
+/// operand is a 16 bit offset, sign says which way it jumps
+fn jump_instruction(name: &str, sign: i64, chunk: &Chunk, offset: usize) -> usize {
+    let jump = ((chunk.code[offset + 1] as u16) << 8) | chunk.code[offset + 2] as u16;
+    let target = offset as i64 + 3 + sign * jump as i64;
+    println!("{:<16} {:4} -> {}", name, offset, target);
+    offset + 3
+}
@@ -0,0 +1,10 @@
+pub mod chunk;
+pub mod compiler;
+pub mod debug;
+pub mod memory;
+pub mod native;
+pub mod object;
+pub mod scanner;
+pub(crate) mod table;
+pub mod value;
+pub mod vm;
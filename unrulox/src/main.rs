@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use unrulox::vm::{InterpretResult, VM};
+
+const USAGE: &str = "Usage: unrulox [--trace] [--disassemble] [--stress-gc] [--log-gc] [--gc-threshold=<bytes>] [script]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut vm = VM::new();
+
+    let (flags, paths): (Vec<&String>, Vec<&String>) = args.iter().partition(|arg| arg.starts_with("--"));
+    for flag in flags {
+        match flag.as_str() {
+            "--trace" => vm.trace_execution = true,
+            "--disassemble" => vm.print_code = true,
+            "--stress-gc" => vm.stress_gc = true,
+            "--log-gc" => vm.log_gc = true,
+            flag if flag.starts_with("--gc-threshold=") => match flag["--gc-threshold=".len()..].parse() {
+                Ok(bytes) => vm.next_gc = bytes,
+                Err(_) => {
+                    eprintln!("{}", USAGE);
+                    process::exit(64);
+                },
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(64);
+            },
+        }
+    }
+
+    match paths.as_slice() {
+        [] => repl(&mut vm),
+        [path] => run_file(&mut vm, path),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(64);
+        },
+    }
+}
+
+fn repl(vm: &mut VM) {
+    let mut line: String = String::new();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        line.clear();
+        if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            println!();
+            break;
+        }
+        vm.interpret(&line);
+    }
+}
+
+fn run_file(vm: &mut VM, path: &str) {
+    let source = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Could not read file \"{}\": {}", path, e);
+            process::exit(74);
+        },
+    };
+    match vm.interpret(&source) {
+        InterpretResult::Ok => {},
+        InterpretResult::CompileError => process::exit(65),
+        InterpretResult::RuntimeError => process::exit(70),
+    }
+}
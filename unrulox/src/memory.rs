@@ -0,0 +1,203 @@
+use crate::object::{
+    object_size, Obj, ObjBoundMethod, ObjClass, ObjClosure, ObjFunction, ObjInstance, ObjNative, ObjString,
+    ObjType, ObjUpvalue,
+};
+use crate::value::Value;
+use crate::vm::VM;
+
+/// after a collection the next one is due once the heap has grown by this factor
+pub const GC_HEAP_GROW_FACTOR: usize = 2;
+
+/// the first collection happens once this many bytes are live, unless VM::next_gc is changed
+pub const GC_INITIAL_THRESHOLD: usize = 1024 * 1024;
+
+// tri-color marking: white objects aren't marked, gray ones are marked and waiting on the gray stack,
+// black ones are marked and have had everything they reference marked too
+
+impl VM {
+
+    /// counts bytes towards the next collection, and runs it first if it's due
+    pub(crate) fn charge(&mut self, size: usize) {
+        self.bytes_allocated += size;
+        if self.stress_gc || self.bytes_allocated > self.next_gc {
+            self.collect_garbage();
+        }
+    }
+
+    pub fn collect_garbage(&mut self) {
+        let before = self.bytes_allocated;
+        if self.log_gc {println!("-- gc begin")};
+
+        self.mark_roots();
+        self.trace_references();
+        self.strings.remove_white();
+        self.sweep();
+
+        self.next_gc = self.bytes_allocated * GC_HEAP_GROW_FACTOR;
+
+        if self.log_gc {
+            println!("-- gc end");
+            println!(
+                "   collected {} bytes (from {} to {}) next at {}",
+                before - self.bytes_allocated, before, self.bytes_allocated, self.next_gc,
+            );
+        }
+    }
+
+    fn mark_roots(&mut self) {
+        let mut slot = self.stack.as_ptr();
+        while slot < self.stack_top as *const Value {
+            unsafe {
+                self.mark_value(*slot);
+                slot = slot.add(1);
+            }
+        }
+
+        for index in 0..self.frames.len() {
+            let closure = self.frames[index].closure;
+            self.mark_object(closure as *mut Obj);
+        }
+
+        let mut upvalue = self.open_upvalues;
+        while !upvalue.is_null() {
+            self.mark_object(upvalue as *mut Obj);
+            upvalue = unsafe { (*upvalue).next };
+        }
+
+        let globals: Vec<_> = self.globals.iter().collect();
+        self.mark_entries(&globals);
+
+        self.mark_object(self.init_string as *mut Obj);
+
+        // functions the compiler is still filling in aren't reachable from anywhere else yet
+        for index in 0..self.compiler_roots.len() {
+            let function = self.compiler_roots[index];
+            self.mark_object(function as *mut Obj);
+        }
+    }
+
+    fn mark_value(&mut self, value: Value) {
+        if value.is_obj() {self.mark_object(value.as_obj())};
+    }
+
+    pub(crate) fn mark_object(&mut self, object: *mut Obj) {
+        if object.is_null() {return};
+        unsafe {
+            if (*object).is_marked {return};
+            if self.log_gc {println!("{:p} mark {}", object, Value::obj(object))};
+            (*object).is_marked = true;
+        }
+        self.gray_stack.push(object);
+    }
+
+    /// table entries are copied out first, marking needs the vm mutably while the table lives inside it
+    fn mark_entries(&mut self, entries: &[(*mut ObjString, Value)]) {
+        for &(key, value) in entries {
+            self.mark_object(key as *mut Obj);
+            self.mark_value(value);
+        }
+    }
+
+    fn trace_references(&mut self) {
+        while let Some(object) = self.gray_stack.pop() {
+            self.blacken_object(object);
+        }
+    }
+
+    /// marks everything the object points to, after this it's black
+    fn blacken_object(&mut self, object: *mut Obj) {
+        if self.log_gc {println!("{:p} blacken {}", object, Value::obj(object))};
+        unsafe {
+            match (*object).type_of {
+                ObjType::BoundMethod => {
+                    let bound = object as *mut ObjBoundMethod;
+                    self.mark_value((*bound).receiver);
+                    self.mark_object((*bound).method as *mut Obj);
+                },
+                ObjType::Class => {
+                    let class = object as *mut ObjClass;
+                    self.mark_object((*class).name as *mut Obj);
+                    let methods: Vec<_> = (*class).methods.iter().collect();
+                    self.mark_entries(&methods);
+                },
+                ObjType::Closure => {
+                    let closure = object as *mut ObjClosure;
+                    self.mark_object((*closure).function as *mut Obj);
+                    for index in 0..(*closure).upvalues.len() {
+                        self.mark_object((&(*closure).upvalues)[index] as *mut Obj);
+                    }
+                },
+                ObjType::Function => {
+                    let function = object as *mut ObjFunction;
+                    self.mark_object((*function).name as *mut Obj);
+                    for index in 0..(*function).chunk.constants.len() {
+                        self.mark_value((&(*function).chunk.constants)[index]);
+                    }
+                },
+                ObjType::Instance => {
+                    let instance = object as *mut ObjInstance;
+                    self.mark_object((*instance).class as *mut Obj);
+                    let fields: Vec<_> = (*instance).fields.iter().collect();
+                    self.mark_entries(&fields);
+                },
+                ObjType::Upvalue => self.mark_value((*(object as *mut ObjUpvalue)).closed),
+                ObjType::Native | ObjType::String => {},
+            }
+        }
+    }
+
+    /// frees every white object and turns the black ones white again for the next cycle
+    fn sweep(&mut self) {
+        let mut previous: *mut Obj = std::ptr::null_mut();
+        let mut object = self.objects;
+        unsafe {
+            while !object.is_null() {
+                if (*object).is_marked {
+                    (*object).is_marked = false;
+                    previous = object;
+                    object = (*object).next;
+                    continue;
+                }
+
+                let unreached = object;
+                object = (*object).next;
+                if previous.is_null() {
+                    self.objects = object;
+                } else {
+                    (*previous).next = object;
+                }
+                self.free_object(unreached);
+            }
+        }
+    }
+
+    fn free_object(&mut self, object: *mut Obj) {
+        unsafe {
+            self.bytes_allocated -= object_size(object);
+            if self.log_gc {println!("{:p} free type {:?}", object, (*object).type_of)};
+
+            match (*object).type_of {
+                ObjType::BoundMethod => drop(Box::from_raw(object as *mut ObjBoundMethod)),
+                ObjType::Class => drop(Box::from_raw(object as *mut ObjClass)),
+                ObjType::Closure => drop(Box::from_raw(object as *mut ObjClosure)),
+                ObjType::Function => drop(Box::from_raw(object as *mut ObjFunction)),
+                ObjType::Instance => drop(Box::from_raw(object as *mut ObjInstance)),
+                ObjType::Native => drop(Box::from_raw(object as *mut ObjNative)),
+                ObjType::String => drop(Box::from_raw(object as *mut ObjString)),
+                ObjType::Upvalue => drop(Box::from_raw(object as *mut ObjUpvalue)),
+            }
+        }
+    }
+
+    /// frees the whole heap, reachable or not, only for when the vm goes away
+    pub(crate) fn free_objects(&mut self) {
+        let mut object = self.objects;
+        while !object.is_null() {
+            let next = unsafe { (*object).next };
+            self.free_object(object);
+            object = next;
+        }
+        self.objects = std::ptr::null_mut();
+        self.gray_stack.clear();
+    }
+}
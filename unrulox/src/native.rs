@@ -0,0 +1,11 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::value::Value;
+
+/// seconds since the unix epoch, same as rulox's clock
+pub fn clock(_arguments: &[Value]) -> Result<Value, String> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => Ok(Value::number(elapsed.as_secs_f64())),
+        Err(e) => Err(format!("Clock went backwards: {}", e)),
+    }
+}
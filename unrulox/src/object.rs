@@ -0,0 +1,283 @@
+use std::fmt;
+use std::mem;
+use std::ptr;
+
+use crate::chunk::Chunk;
+use crate::table::Table;
+use crate::value::Value;
+use crate::vm::VM;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjType {
+    BoundMethod,
+    Class,
+    Closure,
+    Function,
+    Instance,
+    Native,
+    String,
+    Upvalue,
+}
+
+/// header every heap object starts with, `next` threads all of them into one list for the sweeper.
+/// Every object struct is repr(C) with this as its first field, so a *mut Obj can be cast back down
+#[repr(C)]
+#[derive(Debug)]
+pub struct Obj {
+    pub type_of:   ObjType,
+    pub is_marked: bool,
+    pub next:      *mut Obj,
+}
+
+#[repr(C)]
+pub struct ObjString {
+    pub obj:   Obj,
+    pub hash:  u32,
+    pub chars: Box<str>,
+}
+
+#[repr(C)]
+pub struct ObjFunction {
+    pub obj:           Obj,
+    pub arity:         usize,
+    pub upvalue_count: usize,
+    pub chunk:         Chunk,
+    /// null for the top level script
+    pub name:          *mut ObjString,
+}
+
+pub type NativeFn = fn(&[Value]) -> Result<Value, String>;
+
+#[repr(C)]
+pub struct ObjNative {
+    pub obj:      Obj,
+    pub arity:    usize,
+    pub function: NativeFn,
+}
+
+#[repr(C)]
+pub struct ObjClosure {
+    pub obj:      Obj,
+    pub function: *mut ObjFunction,
+    pub upvalues: Vec<*mut ObjUpvalue>,
+}
+
+/// points at a stack slot while open, once closed the value moves into `closed` and location points there
+#[repr(C)]
+pub struct ObjUpvalue {
+    pub obj:      Obj,
+    pub location: *mut Value,
+    pub closed:   Value,
+    /// next open upvalue further down the stack
+    pub next:     *mut ObjUpvalue,
+}
+
+#[repr(C)]
+pub struct ObjClass {
+    pub obj:            Obj,
+    pub name:           *mut ObjString,
+    pub(crate) methods: Table,
+}
+
+#[repr(C)]
+pub struct ObjInstance {
+    pub obj:           Obj,
+    pub class:         *mut ObjClass,
+    pub(crate) fields: Table,
+}
+
+#[repr(C)]
+pub struct ObjBoundMethod {
+    pub obj:      Obj,
+    pub receiver: Value,
+    pub method:   *mut ObjClosure,
+}
+
+impl Obj {
+    fn new(type_of: ObjType) -> Obj {
+        Obj {
+            type_of,
+            is_marked: false,
+            next:      ptr::null_mut(),
+        }
+    }
+}
+
+/// FNV-1a, what clox uses for string keys
+pub fn hash_string(chars: &str) -> u32 {
+    let mut hash: u32 = 2166136261;
+    for byte in chars.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(16777619);
+    }
+    hash
+}
+
+/// bytes the gc charges for an object, the same number is credited back when it's freed
+///
+/// # Safety
+/// `object` has to point at a live object
+pub unsafe fn object_size(object: *mut Obj) -> usize {
+    unsafe {
+        match (*object).type_of {
+            ObjType::BoundMethod => mem::size_of::<ObjBoundMethod>(),
+            ObjType::Class => mem::size_of::<ObjClass>(),
+            ObjType::Closure => mem::size_of::<ObjClosure>(),
+            ObjType::Function => mem::size_of::<ObjFunction>(),
+            ObjType::Instance => mem::size_of::<ObjInstance>(),
+            ObjType::Native => mem::size_of::<ObjNative>(),
+            ObjType::String => {
+                let string = &*(object as *mut ObjString);
+                mem::size_of::<ObjString>() + string.chars.len()
+            },
+            ObjType::Upvalue => mem::size_of::<ObjUpvalue>(),
+        }
+    }
+}
+
+impl VM {
+
+    /// boxes the object, links it into the object list and charges it to the gc,
+    /// which may run a collection first, so anything not yet reachable has to be rooted by the caller
+    fn allocate_object<T>(&mut self, object: T) -> *mut T {
+        let object = Box::into_raw(Box::new(object));
+        let header = object as *mut Obj;
+        let size = unsafe { object_size(header) };
+        self.charge(size);
+
+        unsafe { (*header).next = self.objects };
+        self.objects = header;
+
+        if self.log_gc {
+            println!("{:p} allocate {} for {:?}", header, size, unsafe { (*header).type_of });
+        }
+        object
+    }
+
+    /// interns the string, the chars are copied only if it's not already known
+    pub fn copy_string(&mut self, chars: &str) -> *mut ObjString {
+        let hash = hash_string(chars);
+        let interned = self.strings.find_string(chars, hash);
+        if !interned.is_null() {return interned};
+        self.allocate_string(chars.into(), hash)
+    }
+
+    /// like copy_string, but takes ownership of chars someone already built
+    pub fn take_string(&mut self, chars: String) -> *mut ObjString {
+        let hash = hash_string(&chars);
+        let interned = self.strings.find_string(&chars, hash);
+        if !interned.is_null() {return interned};
+        self.allocate_string(chars.into_boxed_str(), hash)
+    }
+
+    fn allocate_string(&mut self, chars: Box<str>, hash: u32) -> *mut ObjString {
+        let string = self.allocate_object(ObjString {
+            obj: Obj::new(ObjType::String),
+            hash,
+            chars,
+        });
+        // the string is unreachable until the intern table holds it, keep it on the stack meanwhile
+        self.push(Value::obj(string as *mut Obj));
+        self.strings.set(string, Value::nil());
+        self.pop();
+        string
+    }
+
+    pub fn new_function(&mut self) -> *mut ObjFunction {
+        self.allocate_object(ObjFunction {
+            obj:           Obj::new(ObjType::Function),
+            arity:         0,
+            upvalue_count: 0,
+            chunk:         Chunk::new(),
+            name:          ptr::null_mut(),
+        })
+    }
+
+    pub fn new_native(&mut self, arity: usize, function: NativeFn) -> *mut ObjNative {
+        self.allocate_object(ObjNative {
+            obj: Obj::new(ObjType::Native),
+            arity,
+            function,
+        })
+    }
+
+    /// upvalue slots start out null and are filled in by OP_CLOSURE right after
+    ///
+    /// # Safety
+    /// `function` has to point at a live function that's rooted somewhere
+    pub unsafe fn new_closure(&mut self, function: *mut ObjFunction) -> *mut ObjClosure {
+        let upvalue_count = unsafe { (*function).upvalue_count };
+        self.allocate_object(ObjClosure {
+            obj:      Obj::new(ObjType::Closure),
+            function,
+            upvalues: vec![ptr::null_mut(); upvalue_count],
+        })
+    }
+
+    pub(crate) fn new_upvalue(&mut self, slot: *mut Value) -> *mut ObjUpvalue {
+        self.allocate_object(ObjUpvalue {
+            obj:      Obj::new(ObjType::Upvalue),
+            location: slot,
+            closed:   Value::nil(),
+            next:     ptr::null_mut(),
+        })
+    }
+
+    pub(crate) fn new_class(&mut self, name: *mut ObjString) -> *mut ObjClass {
+        self.allocate_object(ObjClass {
+            obj:     Obj::new(ObjType::Class),
+            name,
+            methods: Table::new(),
+        })
+    }
+
+    pub(crate) fn new_instance(&mut self, class: *mut ObjClass) -> *mut ObjInstance {
+        self.allocate_object(ObjInstance {
+            obj:    Obj::new(ObjType::Instance),
+            class,
+            fields: Table::new(),
+        })
+    }
+
+    pub(crate) fn new_bound_method(&mut self, receiver: Value, method: *mut ObjClosure) -> *mut ObjBoundMethod {
+        self.allocate_object(ObjBoundMethod {
+            obj: Obj::new(ObjType::BoundMethod),
+            receiver,
+            method,
+        })
+    }
+}
+
+fn fmt_function(function: *mut ObjFunction, f: &mut fmt::Formatter) -> fmt::Result {
+    unsafe {
+        if (*function).name.is_null() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", (*(*function).name).chars)
+        }
+    }
+}
+
+/// how print shows an object, kept in line with rulox's Display impls
+///
+/// # Safety
+/// `object` has to point at a live object
+pub unsafe fn fmt_object(object: *mut Obj, f: &mut fmt::Formatter) -> fmt::Result {
+    unsafe {
+        match (*object).type_of {
+            ObjType::BoundMethod => {
+                fmt_function((*(*(object as *mut ObjBoundMethod)).method).function, f)
+            },
+            ObjType::Class => write!(f, "{}", (*(*(object as *mut ObjClass)).name).chars),
+            ObjType::Closure => fmt_function((*(object as *mut ObjClosure)).function, f),
+            ObjType::Function => fmt_function(object as *mut ObjFunction, f),
+            ObjType::Instance => {
+                write!(f, "{} instance", (*(*(*(object as *mut ObjInstance)).class).name).chars)
+            },
+            ObjType::Native => write!(f, "<native fn>"),
+            ObjType::String => write!(f, "{}", (*(object as *mut ObjString)).chars),
+            ObjType::Upvalue => write!(f, "upvalue"),
+        }
+    }
+}
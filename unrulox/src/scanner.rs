@@ -0,0 +1,219 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenType {
+
+LeftParen, RightParen, LeftBrace, RightBrace, Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+
+Bang, BangEqual, Equal, EqualEqual, Greater, GreaterEqual, Less, LessEqual,
+
+Identifier, String, Number,
+
+And, Class, Else, False, Fun, For, If, Nil, Or, Print, Return, Super, This, True, Var, While,
+
+Error, Eof
+}
+
+/// tokens borrow their lexeme straight from the source, error tokens carry the message instead
+#[derive(Debug, Clone, Copy)]
+pub struct Token<'a> {
+    pub type_of: TokenType,
+    pub lexeme:  &'a str,
+    pub line:    u32,
+}
+
+/// hands out one token at a time, the compiler pulls them as it needs them
+pub struct Scanner<'a> {
+    source:  &'a str,
+    start:   usize,
+    current: usize,
+    line:    u32,
+}
+
+impl<'a> Scanner<'a> {
+
+    pub fn new(source: &'a str) -> Self {
+        Scanner {
+            source,
+            start:   0,
+            current: 0,
+            line:    1,
+        }
+    }
+
+    pub fn scan_token(&mut self) -> Token<'a> {
+        self.skip_whitespace();
+        self.start = self.current;
+
+        if self.is_at_end() {return self.make_token(TokenType::Eof)};
+
+        let c = self.advance();
+        if is_alpha(c) {return self.identifier()};
+        if c.is_ascii_digit() {return self.number()};
+
+        match c {
+            b'(' => self.make_token(TokenType::LeftParen),
+            b')' => self.make_token(TokenType::RightParen),
+            b'{' => self.make_token(TokenType::LeftBrace),
+            b'}' => self.make_token(TokenType::RightBrace),
+            b';' => self.make_token(TokenType::Semicolon),
+            b',' => self.make_token(TokenType::Comma),
+            b'.' => self.make_token(TokenType::Dot),
+            b'-' => self.make_token(TokenType::Minus),
+            b'+' => self.make_token(TokenType::Plus),
+            b'/' => self.make_token(TokenType::Slash),
+            b'*' => self.make_token(TokenType::Star),
+            b'!' => {
+                let t = if self.match_next(b'=') {TokenType::BangEqual} else {TokenType::Bang};
+                self.make_token(t)
+            },
+            b'=' => {
+                let t = if self.match_next(b'=') {TokenType::EqualEqual} else {TokenType::Equal};
+                self.make_token(t)
+            },
+            b'<' => {
+                let t = if self.match_next(b'=') {TokenType::LessEqual} else {TokenType::Less};
+                self.make_token(t)
+            },
+            b'>' => {
+                let t = if self.match_next(b'=') {TokenType::GreaterEqual} else {TokenType::Greater};
+                self.make_token(t)
+            },
+            b'"' => self.string(),
+            _ => self.error_token("Unexpected character."),
+        }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current >= self.source.len()
+    }
+
+    fn advance(&mut self) -> u8 {
+        self.current += 1;
+        self.source.as_bytes()[self.current - 1]
+    }
+
+    fn peek(&self) -> u8 {
+        self.source.as_bytes().get(self.current).copied().unwrap_or(b'\0')
+    }
+
+    fn peek_next(&self) -> u8 {
+        self.source.as_bytes().get(self.current + 1).copied().unwrap_or(b'\0')
+    }
+
+    fn match_next(&mut self, expected: u8) -> bool {
+        if self.is_at_end() || self.peek() != expected {return false};
+        self.current += 1;
+        true
+    }
+
+    fn make_token(&self, type_of: TokenType) -> Token<'a> {
+        Token {
+            type_of,
+            lexeme: &self.source[self.start..self.current],
+            line:   self.line,
+        }
+    }
+
+    fn error_token(&self, message: &'static str) -> Token<'a> {
+        Token {
+            type_of: TokenType::Error,
+            lexeme:  message,
+            line:    self.line,
+        }
+    }
+
+    /// eats spaces, newlines and comments between tokens
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                b' ' | b'\r' | b'\t' => { self.advance(); },
+                b'\n' => {
+                    self.line += 1;
+                    self.advance();
+                },
+                b'/' if self.peek_next() == b'/' => {
+                    while self.peek() != b'\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                },
+                _ => return,
+            }
+        }
+    }
+
+    fn string(&mut self) -> Token<'a> {
+        while self.peek() != b'"' && !self.is_at_end() {
+            if self.peek() == b'\n' {self.line += 1};
+            self.advance();
+        }
+        if self.is_at_end() {return self.error_token("Unterminated string.")};
+
+        // the closing quote
+        self.advance();
+        self.make_token(TokenType::String)
+    }
+
+    fn number(&mut self) -> Token<'a> {
+        while self.peek().is_ascii_digit() {
+            self.advance();
+        }
+        if self.peek() == b'.' && self.peek_next().is_ascii_digit() {
+            self.advance();
+            while self.peek().is_ascii_digit() {
+                self.advance();
+            }
+        }
+        // `12abc` is a mistake rather than a number and then an identifier
+        if is_alpha(self.peek()) {
+            while is_alpha(self.peek()) || self.peek().is_ascii_digit() {
+                self.advance();
+            }
+            return self.error_token("Malformed number.");
+        }
+        self.make_token(TokenType::Number)
+    }
+
+    fn identifier(&mut self) -> Token<'a> {
+        while is_alpha(self.peek()) || self.peek().is_ascii_digit() {
+            self.advance();
+        }
+        self.make_token(self.identifier_type())
+    }
+
+    /// hand rolled trie over the keywords, same idea as clox
+    fn identifier_type(&self) -> TokenType {
+        let text = &self.source.as_bytes()[self.start..self.current];
+        let keyword = |rest: &str, start: usize, type_of: TokenType| {
+            if &text[start..] == rest.as_bytes() {type_of} else {TokenType::Identifier}
+        };
+
+        match text[0] {
+            b'a' => keyword("nd", 1, TokenType::And),
+            b'c' => keyword("lass", 1, TokenType::Class),
+            b'e' => keyword("lse", 1, TokenType::Else),
+            b'f' if text.len() > 1 => match text[1] {
+                b'a' => keyword("lse", 2, TokenType::False),
+                b'o' => keyword("r", 2, TokenType::For),
+                b'u' => keyword("n", 2, TokenType::Fun),
+                _ => TokenType::Identifier,
+            },
+            b'i' => keyword("f", 1, TokenType::If),
+            b'n' => keyword("il", 1, TokenType::Nil),
+            b'o' => keyword("r", 1, TokenType::Or),
+            b'p' => keyword("rint", 1, TokenType::Print),
+            b'r' => keyword("eturn", 1, TokenType::Return),
+            b's' => keyword("uper", 1, TokenType::Super),
+            b't' if text.len() > 1 => match text[1] {
+                b'h' => keyword("is", 2, TokenType::This),
+                b'r' => keyword("ue", 2, TokenType::True),
+                _ => TokenType::Identifier,
+            },
+            b'v' => keyword("ar", 1, TokenType::Var),
+            b'w' => keyword("hile", 1, TokenType::While),
+            _ => TokenType::Identifier,
+        }
+    }
+}
+
+fn is_alpha(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
@@ -0,0 +1,156 @@
+use std::ptr;
+
+use crate::object::ObjString;
+use crate::value::Value;
+
+const TABLE_MAX_LOAD: f64 = 0.75;
+
+/// an empty bucket has a null key and a nil value, a tombstone has a null key and a true value
+#[derive(Clone, Copy)]
+struct Entry {
+    key:   *mut ObjString,
+    value: Value,
+}
+
+impl Entry {
+    fn empty() -> Entry {
+        Entry { key: ptr::null_mut(), value: Value::nil() }
+    }
+
+    fn is_tombstone(&self) -> bool {
+        self.key.is_null() && !self.value.is_nil()
+    }
+}
+
+/// open addressing with linear probing, keyed on interned strings so keys compare by pointer.
+/// The capacity is always a power of two, which lets probing mask instead of divide.
+/// Keys are read through to get their hash, so only strings the heap allocated can go in, which is why it
+/// isn't usable outside the crate
+#[derive(Default)]
+pub struct Table {
+    /// live entries plus tombstones, so the load factor accounts for both
+    count:   usize,
+    entries: Vec<Entry>,
+}
+
+impl Table {
+
+    pub fn new() -> Self {
+        Table {
+            count:   0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// the bucket holding key, or the one it should go in, reusing the first tombstone passed on the way
+    fn find_entry(entries: &[Entry], key: *mut ObjString) -> usize {
+        let mask = entries.len() - 1;
+        let mut index = unsafe { (*key).hash } as usize & mask;
+        let mut tombstone: Option<usize> = None;
+
+        loop {
+            let entry = &entries[index];
+            if entry.key.is_null() {
+                if !entry.is_tombstone() {
+                    return tombstone.unwrap_or(index);
+                }
+                if tombstone.is_none() {tombstone = Some(index)};
+            } else if entry.key == key {
+                return index;
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    /// rehashes into a bigger array, tombstones are dropped along the way so count is recomputed
+    fn adjust_capacity(&mut self, capacity: usize) {
+        let mut entries = vec![Entry::empty(); capacity];
+        self.count = 0;
+        for entry in self.entries.iter().filter(|entry| !entry.key.is_null()) {
+            let index = Self::find_entry(&entries, entry.key);
+            entries[index] = *entry;
+            self.count += 1;
+        }
+        self.entries = entries;
+    }
+
+    pub fn get(&self, key: *mut ObjString) -> Option<Value> {
+        if self.count == 0 {return None};
+
+        let entry = &self.entries[Self::find_entry(&self.entries, key)];
+        if entry.key.is_null() {None} else {Some(entry.value)}
+    }
+
+    /// returns true when the key wasn't in the table before
+    pub fn set(&mut self, key: *mut ObjString, value: Value) -> bool {
+        if (self.count + 1) as f64 > self.entries.len() as f64 * TABLE_MAX_LOAD {
+            let capacity = if self.entries.len() < 8 {8} else {self.entries.len() * 2};
+            self.adjust_capacity(capacity);
+        }
+
+        let index = Self::find_entry(&self.entries, key);
+        let entry = &mut self.entries[index];
+        let is_new_key = entry.key.is_null();
+        // reusing a tombstone doesn't change the count, it was already counted
+        if is_new_key && entry.value.is_nil() {self.count += 1};
+
+        entry.key = key;
+        entry.value = value;
+        is_new_key
+    }
+
+    /// leaves a tombstone behind so probe sequences running through this bucket aren't cut short
+    pub fn delete(&mut self, key: *mut ObjString) -> bool {
+        if self.count == 0 {return false};
+
+        let index = Self::find_entry(&self.entries, key);
+        let entry = &mut self.entries[index];
+        if entry.key.is_null() {return false};
+
+        entry.key = ptr::null_mut();
+        entry.value = Value::boolean(true);
+        true
+    }
+
+    pub fn add_all(&self, to: &mut Table) {
+        for (key, value) in self.iter() {
+            to.set(key, value);
+        }
+    }
+
+    /// the one lookup that compares characters instead of pointers, it's how strings get interned
+    pub fn find_string(&self, chars: &str, hash: u32) -> *mut ObjString {
+        if self.count == 0 {return ptr::null_mut()};
+
+        let mask = self.entries.len() - 1;
+        let mut index = hash as usize & mask;
+        loop {
+            let entry = &self.entries[index];
+            if entry.key.is_null() {
+                if !entry.is_tombstone() {return ptr::null_mut()};
+            } else {
+                let key = unsafe { &*entry.key };
+                if key.hash == hash && *key.chars == *chars {
+                    return entry.key;
+                }
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    /// drops every key the collector didn't reach, the intern table holds its strings weakly
+    pub fn remove_white(&mut self) {
+        for index in 0..self.entries.len() {
+            let key = self.entries[index].key;
+            if !key.is_null() && unsafe { !(*key).obj.is_marked } {
+                self.delete(key);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (*mut ObjString, Value)> + '_ {
+        self.entries.iter()
+            .filter(|entry| !entry.key.is_null())
+            .map(|entry| (entry.key, entry.value))
+    }
+}
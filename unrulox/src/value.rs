@@ -0,0 +1,231 @@
+use std::fmt;
+
+use crate::object::{fmt_object, Obj, ObjString, ObjType};
+
+#[cfg(not(feature = "nan-boxing"))]
+#[derive(Clone, Copy)]
+enum Repr {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    // owned by the vm that allocated it, see object.rs
+    Obj(*mut Obj),
+}
+
+/// the vm only ever touches values through the methods below, so the representation can change,
+/// the nan-boxing feature swaps this enum for a single u64. It's private either way, object values can
+/// only be made inside the crate, out of objects the heap allocated
+#[cfg(not(feature = "nan-boxing"))]
+#[derive(Clone, Copy)]
+pub struct Value(Repr);
+
+#[cfg(not(feature = "nan-boxing"))]
+impl Value {
+
+    pub fn nil() -> Value {
+        Value(Repr::Nil)
+    }
+
+    pub fn boolean(b: bool) -> Value {
+        Value(Repr::Bool(b))
+    }
+
+    pub fn number(n: f64) -> Value {
+        Value(Repr::Number(n))
+    }
+
+    /// the object has to be one the heap allocated, everything that looks at an object value reads through it
+    pub(crate) fn obj(object: *mut Obj) -> Value {
+        Value(Repr::Obj(object))
+    }
+
+    pub fn is_nil(self) -> bool {
+        matches!(self.0, Repr::Nil)
+    }
+
+    pub fn is_bool(self) -> bool {
+        matches!(self.0, Repr::Bool(_))
+    }
+
+    pub fn is_number(self) -> bool {
+        matches!(self.0, Repr::Number(_))
+    }
+
+    pub fn is_obj(self) -> bool {
+        matches!(self.0, Repr::Obj(_))
+    }
+
+    /// only meaningful after checking is_bool
+    pub fn as_bool(self) -> bool {
+        match self.0 {
+            Repr::Bool(b) => b,
+            _ => false,
+        }
+    }
+
+    /// only meaningful after checking is_number
+    pub fn as_number(self) -> f64 {
+        match self.0 {
+            Repr::Number(n) => n,
+            _ => 0.0,
+        }
+    }
+
+    /// only meaningful after checking is_obj
+    pub(crate) fn as_obj(self) -> *mut Obj {
+        match self.0 {
+            Repr::Obj(object) => object,
+            _ => std::ptr::null_mut(),
+        }
+    }
+}
+
+// any double whose exponent bits are all set and has the quiet bit plus the one after it set is never
+// produced by arithmetic, so those bit patterns are free to mean something else.
+// Objects also set the sign bit and keep their pointer in the low 48 bits,
+// nil, false and true are the quiet nan with a small tag in the lowest bits
+#[cfg(feature = "nan-boxing")]
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+#[cfg(feature = "nan-boxing")]
+const QNAN: u64 = 0x7ffc_0000_0000_0000;
+#[cfg(feature = "nan-boxing")]
+const TAG_NIL: u64 = 1;
+#[cfg(feature = "nan-boxing")]
+const TAG_FALSE: u64 = 2;
+#[cfg(feature = "nan-boxing")]
+const TAG_TRUE: u64 = 3;
+
+/// the vm only ever touches values through the methods below, so the representation can change,
+/// this is the nan-boxed one where every value fits in a u64
+#[cfg(feature = "nan-boxing")]
+#[derive(Clone, Copy)]
+pub struct Value(u64);
+
+#[cfg(feature = "nan-boxing")]
+impl Value {
+
+    pub fn nil() -> Value {
+        Value(QNAN | TAG_NIL)
+    }
+
+    pub fn boolean(b: bool) -> Value {
+        Value(QNAN | if b {TAG_TRUE} else {TAG_FALSE})
+    }
+
+    /// arithmetic never makes a nan with the bits objects use, but a native could hand one over, so every
+    /// nan becomes the one arithmetic makes
+    pub fn number(n: f64) -> Value {
+        if n.is_nan() {
+            Value(f64::NAN.to_bits())
+        } else {
+            Value(n.to_bits())
+        }
+    }
+
+    /// the object has to be one the heap allocated, everything that looks at an object value reads through it
+    pub(crate) fn obj(object: *mut Obj) -> Value {
+        debug_assert!(object as u64 & (SIGN_BIT | QNAN) == 0, "pointer doesn't fit in 48 bits");
+        Value(SIGN_BIT | QNAN | object as u64)
+    }
+
+    pub fn is_nil(self) -> bool {
+        self.0 == QNAN | TAG_NIL
+    }
+
+    /// false and true only differ in the lowest bit
+    pub fn is_bool(self) -> bool {
+        self.0 | 1 == QNAN | TAG_TRUE
+    }
+
+    pub fn is_number(self) -> bool {
+        self.0 & QNAN != QNAN
+    }
+
+    pub fn is_obj(self) -> bool {
+        self.0 & (SIGN_BIT | QNAN) == SIGN_BIT | QNAN
+    }
+
+    /// only meaningful after checking is_bool
+    pub fn as_bool(self) -> bool {
+        self.0 == QNAN | TAG_TRUE
+    }
+
+    /// only meaningful after checking is_number
+    pub fn as_number(self) -> f64 {
+        f64::from_bits(self.0)
+    }
+
+    /// only meaningful after checking is_obj
+    pub(crate) fn as_obj(self) -> *mut Obj {
+        (self.0 & !(SIGN_BIT | QNAN)) as *mut Obj
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_nil() {
+            write!(f, "Nil")
+        } else if self.is_bool() {
+            write!(f, "Bool({})", self.as_bool())
+        } else if self.is_number() {
+            write!(f, "Number({:?})", self.as_number())
+        } else {
+            write!(f, "Obj({:p})", self.as_obj())
+        }
+    }
+}
+
+impl Value {
+
+    pub(crate) fn is_obj_type(self, type_of: ObjType) -> bool {
+        self.is_obj() && unsafe { (*self.as_obj()).type_of == type_of }
+    }
+
+    pub(crate) fn is_string(self) -> bool {
+        self.is_obj_type(ObjType::String)
+    }
+
+    /// only meaningful after checking is_string
+    pub(crate) fn as_string(self) -> *mut ObjString {
+        self.as_obj() as *mut ObjString
+    }
+
+    /// the chars of a string value
+    ///
+    /// # Safety
+    /// the value has to be a string, and nothing can collect it for as long as the `&str` is kept
+    pub(crate) unsafe fn as_str<'a>(self) -> &'a str {
+        unsafe { &(*self.as_string()).chars }
+    }
+
+    /// nil and false are falsey, everything else is truthy
+    pub fn is_falsey(self) -> bool {
+        self.is_nil() || (self.is_bool() && !self.as_bool())
+    }
+}
+
+/// numbers compare as doubles so nan != nan in either representation,
+/// everything else is equal when it's the same nil, boolean or object. Strings are interned, so that covers them too
+pub fn values_equal(a: Value, b: Value) -> bool {
+    if a.is_number() && b.is_number() {
+        a.as_number() == b.as_number()
+    } else if a.is_nil() || a.is_bool() {
+        a.is_nil() == b.is_nil() && a.is_bool() == b.is_bool() && a.as_bool() == b.as_bool()
+    } else {
+        b.is_obj() && a.as_obj() == b.as_obj()
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_nil() {
+            write!(f, "nil")
+        } else if self.is_bool() {
+            write!(f, "{}", self.as_bool())
+        } else if self.is_number() {
+            write!(f, "{}", self.as_number())
+        } else {
+            unsafe { fmt_object(self.as_obj(), f) }
+        }
+    }
+}
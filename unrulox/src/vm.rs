@@ -0,0 +1,725 @@
+use std::io::{self, Write};
+use std::ptr;
+
+use crate::chunk::{Chunk, OpCode};
+use crate::compiler;
+use crate::debug;
+use crate::memory::GC_INITIAL_THRESHOLD;
+use crate::native;
+use crate::object::{
+    NativeFn, Obj, ObjBoundMethod, ObjClass, ObjClosure, ObjFunction, ObjInstance, ObjNative, ObjString, ObjType,
+    ObjUpvalue,
+};
+use crate::table::Table;
+use crate::value::{values_equal, Value};
+
+pub const FRAMES_MAX: usize = 64;
+pub const STACK_MAX: usize = FRAMES_MAX * (u8::MAX as usize + 1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpretResult {
+    Ok,
+    CompileError,
+    RuntimeError,
+}
+
+/// one per function call that hasn't returned yet
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CallFrame {
+    pub(crate) closure: *mut ObjClosure,
+    /// where the caller picks up again, only kept up to date while another frame is running
+    ip:      *const u8,
+    /// the callee's window into the value stack, slot 0 is the callee itself
+    slots:   *mut Value,
+}
+
+/// stack based bytecode interpreter, ip and stack_top are raw pointers just like in clox
+pub struct VM {
+    /// dump the value stack and each instruction as it runs
+    pub trace_execution: bool,
+    /// disassemble every chunk the compiler finishes
+    pub print_code:      bool,
+    /// collect before every single allocation, shakes out values that aren't rooted
+    pub stress_gc:       bool,
+    /// print what the collector marks and frees
+    pub log_gc:          bool,
+    /// bytes allocated before the next collection kicks in, grows with the live heap after each one
+    pub next_gc:         usize,
+    /// where print writes to
+    pub(crate) stdout:   Box<dyn Write>,
+    /// compile and runtime errors end up here
+    pub(crate) stderr:   Box<dyn Write>,
+    // chunk, ip and slots cache the innermost frame, see load_frame
+    chunk:     *const Chunk,
+    ip:        *const u8,
+    slots:     *mut Value,
+    pub(crate) frames:    Vec<CallFrame>,
+    // boxed so the stack never moves out from under stack_top
+    pub(crate) stack:     Box<[Value]>,
+    pub(crate) stack_top: *mut Value,
+    pub(crate) globals:   Table,
+    /// every interned string, held weakly so unreachable ones still get collected
+    pub(crate) strings:   Table,
+    /// "init", interned once so looking up initializers doesn't hash every time
+    pub(crate) init_string: *mut ObjString,
+    /// head of the intrusive list running through every object the vm allocated
+    pub(crate) objects:   *mut Obj,
+    /// upvalues still pointing into the stack, sorted so the topmost slot comes first
+    pub(crate) open_upvalues:   *mut ObjUpvalue,
+    pub(crate) gray_stack:      Vec<*mut Obj>,
+    pub(crate) bytes_allocated: usize,
+    /// functions the compiler is in the middle of building, innermost last
+    pub(crate) compiler_roots:  Vec<*mut ObjFunction>,
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VM {
+
+    pub fn new() -> Self {
+        Self::with_output(Box::new(io::stdout()), Box::new(io::stderr()))
+    }
+
+    /// for running lox as a library, print statements go to stdout and errors to stderr
+    pub fn with_output(stdout: Box<dyn Write>, stderr: Box<dyn Write>) -> Self {
+        let mut stack = vec![Value::nil(); STACK_MAX].into_boxed_slice();
+        let stack_top = stack.as_mut_ptr();
+        let mut vm = VM {
+            trace_execution: false,
+            print_code:      false,
+            stress_gc:       false,
+            log_gc:          false,
+            next_gc:         GC_INITIAL_THRESHOLD,
+            stdout,
+            stderr,
+            chunk:  ptr::null(),
+            ip:     ptr::null(),
+            slots:  ptr::null_mut(),
+            frames: Vec::with_capacity(FRAMES_MAX),
+            stack,
+            stack_top,
+            globals: Table::new(),
+            strings: Table::new(),
+            init_string: ptr::null_mut(),
+            objects: ptr::null_mut(),
+            open_upvalues:   ptr::null_mut(),
+            gray_stack:      Vec::new(),
+            bytes_allocated: 0,
+            compiler_roots:  Vec::new(),
+        };
+        vm.init_string = vm.copy_string("init");
+        vm.define_native("clock", 0, native::clock);
+        vm
+    }
+
+    pub fn interpret(&mut self, source: &str) -> InterpretResult {
+        let Some(function) = compiler::compile(source, self) else {return InterpretResult::CompileError};
+
+        // the function stays on the stack while its closure is allocated, so the gc can see it
+        self.push(Value::obj(function as *mut Obj));
+        let closure = unsafe { self.new_closure(function) };
+        self.pop();
+        self.push(Value::obj(closure as *mut Obj));
+        self.call(closure, 0);
+
+        let result = self.run();
+        self.reset_stack();
+        result
+    }
+
+    /// makes a host function callable from lox as a global
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        // both go on the stack so neither is collected while the other is allocated
+        let name = self.copy_string(name);
+        self.push(Value::obj(name as *mut Obj));
+        let native = self.new_native(arity, function);
+        self.push(Value::obj(native as *mut Obj));
+        self.globals.set(name, Value::obj(native as *mut Obj));
+        self.pop();
+        self.pop();
+    }
+
+    fn reset_stack(&mut self) {
+        self.stack_top = self.stack.as_mut_ptr();
+        self.frames.clear();
+        self.open_upvalues = ptr::null_mut();
+        self.chunk = ptr::null();
+        self.ip = ptr::null();
+        self.slots = ptr::null_mut();
+    }
+
+    /// points the cached registers at the innermost frame
+    fn load_frame(&mut self) {
+        let frame = *self.frames.last().expect("no frame to run");
+        unsafe {
+            self.chunk = &(*(*frame.closure).function).chunk;
+        }
+        self.ip = frame.ip;
+        self.slots = frame.slots;
+    }
+
+    /// writes the cached ip back, has to happen before another frame is pushed or the stack is walked
+    fn save_frame(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = self.ip;
+        }
+    }
+
+    /// run reports a full stack as an error before it gets here, this only stops a bug in that from writing past the end
+    pub(crate) fn push(&mut self, value: Value) {
+        assert!(self.stack_len() < STACK_MAX, "value stack overflow");
+        unsafe {
+            self.stack_top.write(value);
+            self.stack_top = self.stack_top.add(1);
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Value {
+        unsafe {
+            self.stack_top = self.stack_top.sub(1);
+            self.stack_top.read()
+        }
+    }
+
+    /// distance 0 is the top of the stack
+    fn peek(&self, distance: usize) -> Value {
+        unsafe { self.stack_top.sub(1 + distance).read() }
+    }
+
+    fn stack_len(&self) -> usize {
+        unsafe { self.stack_top.offset_from(self.stack.as_ptr()) as usize }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        unsafe {
+            let byte = *self.ip;
+            self.ip = self.ip.add(1);
+            byte
+        }
+    }
+
+    fn read_short(&mut self) -> u16 {
+        let high = self.read_byte() as u16;
+        let low = self.read_byte() as u16;
+        (high << 8) | low
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_byte() as usize;
+        unsafe {
+            let chunk = &*self.chunk;
+            *chunk.constants.get_unchecked(index)
+        }
+    }
+
+    /// the compiler only ever emits string constants as names
+    fn read_string(&mut self) -> *mut ObjString {
+        self.read_constant().as_string()
+    }
+
+    /// the operands are copied out before allocating, so they're free to be collected by then
+    fn concatenate(&mut self) {
+        let b = self.pop();
+        let a = self.pop();
+        // both were strings on the stack a moment ago, and nothing allocates until they're copied
+        let joined = unsafe { [a.as_str(), b.as_str()].concat() };
+        let string = self.take_string(joined);
+        self.push(Value::obj(string as *mut Obj));
+    }
+
+    /// prints the message and a trace with the innermost call first
+    fn runtime_error(&mut self, message: &str) {
+        writeln!(self.stderr, "{}", message).expect("could not write error");
+
+        self.save_frame();
+        for frame in self.frames.iter().rev() {
+            unsafe {
+                let function = (*frame.closure).function;
+                let chunk = &(*function).chunk;
+                let offset = frame.ip.offset_from(chunk.code.as_ptr()) as usize - 1;
+                let line = chunk.get_line(offset);
+                if (*function).name.is_null() {
+                    writeln!(self.stderr, "[line {}] in script", line).expect("could not write error");
+                } else {
+                    writeln!(self.stderr, "[line {}] in {}()", line, (*(*function).name).chars).expect("could not write error");
+                }
+            }
+        }
+        self.reset_stack();
+    }
+
+    /// pushes a frame for the closure, its arguments are already on the stack above it
+    fn call(&mut self, closure: *mut ObjClosure, arg_count: usize) -> bool {
+        let arity = unsafe { (*(*closure).function).arity };
+        if arg_count != arity {
+            self.runtime_error(&format!("Expected {} arguments but got {}.", arity, arg_count));
+            return false;
+        }
+        if self.frames.len() == FRAMES_MAX {
+            self.runtime_error("Stack overflow.");
+            return false;
+        }
+
+        self.save_frame();
+        self.frames.push(CallFrame {
+            closure,
+            ip:    unsafe { (*(*closure).function).chunk.code.as_ptr() },
+            slots: unsafe { self.stack_top.sub(arg_count + 1) },
+        });
+        self.load_frame();
+        true
+    }
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> bool {
+        if callee.is_obj() {
+            match unsafe { (*callee.as_obj()).type_of } {
+                ObjType::BoundMethod => {
+                    let bound = callee.as_obj() as *mut ObjBoundMethod;
+                    // the receiver takes the callee's slot, which is where the method expects this
+                    unsafe {
+                        *self.stack_top.sub(arg_count + 1) = (*bound).receiver;
+                        return self.call((*bound).method, arg_count);
+                    }
+                },
+                ObjType::Class => return self.call_class(callee.as_obj() as *mut ObjClass, arg_count),
+                ObjType::Closure => return self.call(callee.as_obj() as *mut ObjClosure, arg_count),
+                ObjType::Native => return self.call_native(callee.as_obj() as *mut ObjNative, arg_count),
+                _ => {},
+            }
+        }
+        self.runtime_error("Can only call functions and classes.");
+        false
+    }
+
+    /// the new instance replaces the class on the stack, then init runs on it if the class has one
+    fn call_class(&mut self, class: *mut ObjClass, arg_count: usize) -> bool {
+        let instance = self.new_instance(class);
+        unsafe { *self.stack_top.sub(arg_count + 1) = Value::obj(instance as *mut Obj) };
+
+        match unsafe { (*class).methods.get(self.init_string) } {
+            Some(initializer) => self.call(initializer.as_obj() as *mut ObjClosure, arg_count),
+            None if arg_count != 0 => {
+                self.runtime_error(&format!("Expected 0 arguments but got {}.", arg_count));
+                false
+            },
+            None => true,
+        }
+    }
+
+    /// a.b() in one step, skips the bound method get + call would allocate
+    fn invoke(&mut self, name: *mut ObjString, arg_count: usize) -> bool {
+        let receiver = self.peek(arg_count);
+        if !receiver.is_obj_type(ObjType::Instance) {
+            self.runtime_error("Only instances have properties.");
+            return false;
+        }
+
+        let instance = receiver.as_obj() as *mut ObjInstance;
+        // a field holding something callable shadows the method
+        if let Some(value) = unsafe { (*instance).fields.get(name) } {
+            unsafe { *self.stack_top.sub(arg_count + 1) = value };
+            return self.call_value(value, arg_count);
+        }
+        self.invoke_from_class(unsafe { (*instance).class }, name, arg_count)
+    }
+
+    fn invoke_from_class(&mut self, class: *mut ObjClass, name: *mut ObjString, arg_count: usize) -> bool {
+        match unsafe { (*class).methods.get(name) } {
+            Some(method) => self.call(method.as_obj() as *mut ObjClosure, arg_count),
+            None => {
+                self.runtime_error(&format!("Undefined property '{}'.", unsafe { &(*name).chars }));
+                false
+            },
+        }
+    }
+
+    /// swaps the instance on top of the stack for the method bound to it
+    fn bind_method(&mut self, class: *mut ObjClass, name: *mut ObjString) -> bool {
+        let Some(method) = (unsafe { (*class).methods.get(name) }) else {
+            self.runtime_error(&format!("Undefined property '{}'.", unsafe { &(*name).chars }));
+            return false;
+        };
+
+        let bound = self.new_bound_method(self.peek(0), method.as_obj() as *mut ObjClosure);
+        self.pop();
+        self.push(Value::obj(bound as *mut Obj));
+        true
+    }
+
+    /// the closure on top of the stack becomes a method of the class just under it
+    fn define_method(&mut self, name: *mut ObjString) {
+        let method = self.peek(0);
+        let class = self.peek(1).as_obj() as *mut ObjClass;
+        unsafe { (*class).methods.set(name, method) };
+        self.pop();
+    }
+
+    /// natives run right away on a slice of the stack, no frame needed
+    fn call_native(&mut self, native: *mut ObjNative, arg_count: usize) -> bool {
+        let (arity, function) = unsafe { ((*native).arity, (*native).function) };
+        if arg_count != arity {
+            self.runtime_error(&format!("Expected {} arguments but got {}.", arity, arg_count));
+            return false;
+        }
+
+        let arguments = unsafe { std::slice::from_raw_parts(self.stack_top.sub(arg_count), arg_count) };
+        match function(arguments) {
+            Ok(result) => {
+                self.stack_top = unsafe { self.stack_top.sub(arg_count + 1) };
+                self.push(result);
+                true
+            },
+            Err(message) => {
+                self.runtime_error(&message);
+                false
+            },
+        }
+    }
+
+    /// reuses the open upvalue for the slot if there is one, so every closure sees the same variable
+    fn capture_upvalue(&mut self, local: *mut Value) -> *mut ObjUpvalue {
+        let mut previous: *mut ObjUpvalue = ptr::null_mut();
+        let mut upvalue = self.open_upvalues;
+        unsafe {
+            while !upvalue.is_null() && (*upvalue).location > local {
+                previous = upvalue;
+                upvalue = (*upvalue).next;
+            }
+            if !upvalue.is_null() && (*upvalue).location == local {
+                return upvalue;
+            }
+        }
+
+        let created = self.new_upvalue(local);
+        unsafe {
+            (*created).next = upvalue;
+            if previous.is_null() {
+                self.open_upvalues = created;
+            } else {
+                (*previous).next = created;
+            }
+        }
+        created
+    }
+
+    /// hoists every open upvalue at or above last off the stack and into the upvalue itself
+    fn close_upvalues(&mut self, last: *mut Value) {
+        unsafe {
+            while !self.open_upvalues.is_null() && (*self.open_upvalues).location >= last {
+                let upvalue = self.open_upvalues;
+                (*upvalue).closed = *(*upvalue).location;
+                (*upvalue).location = &mut (*upvalue).closed;
+                self.open_upvalues = (*upvalue).next;
+            }
+        }
+    }
+
+    fn current_closure(&self) -> *mut ObjClosure {
+        unsafe { self.frames.last().unwrap_unchecked().closure }
+    }
+
+    /// pops two numbers, or reports an error and bails out of run
+    fn binary_operands(&mut self) -> Option<(f64, f64)> {
+        if !self.peek(0).is_number() || !self.peek(1).is_number() {
+            self.runtime_error("Operands must be numbers.");
+            return None;
+        }
+        let b = self.pop().as_number();
+        let a = self.pop().as_number();
+        Some((a, b))
+    }
+
+    /// prints the stack bottom to top, then the instruction about to run
+    fn trace_instruction(&self) {
+        print!("          ");
+        let mut slot = self.stack.as_ptr();
+        while slot < self.stack_top as *const Value {
+            unsafe {
+                print!("[ {} ]", *slot);
+                slot = slot.add(1);
+            }
+        }
+        println!();
+
+        unsafe {
+            let chunk = &*self.chunk;
+            debug::disassemble_instruction(chunk, self.ip.offset_from(chunk.code.as_ptr()) as usize);
+        }
+    }
+
+    fn run(&mut self) -> InterpretResult {
+        loop {
+            // no instruction ends up more than one slot above where it started, even counting what it keeps on
+            // the stack for the gc while allocating, so one free slot here is enough for any of them
+            if self.stack_len() == STACK_MAX {
+                self.runtime_error("Stack overflow.");
+                return InterpretResult::RuntimeError;
+            }
+            if self.trace_execution {self.trace_instruction()};
+            let instruction = unsafe { OpCode::from_byte_unchecked(self.read_byte()) };
+            match instruction {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                },
+                OpCode::Nil => self.push(Value::nil()),
+                OpCode::True => self.push(Value::boolean(true)),
+                OpCode::False => self.push(Value::boolean(false)),
+                OpCode::Pop => { self.pop(); },
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let value = unsafe { *self.slots.add(slot) };
+                    self.push(value);
+                },
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    unsafe { *self.slots.add(slot) = self.peek(0) };
+                },
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(name) {
+                        Some(value) => self.push(value),
+                        None => {
+                            self.runtime_error(&format!("Undefined variable '{}'.", unsafe { &(*name).chars }));
+                            return InterpretResult::RuntimeError;
+                        },
+                    }
+                },
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0);
+                    self.globals.set(name, value);
+                    self.pop();
+                },
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0);
+                    // setting a brand new key means it was never defined, so undo it
+                    if self.globals.set(name, value) {
+                        self.globals.delete(name);
+                        self.runtime_error(&format!("Undefined variable '{}'.", unsafe { &(*name).chars }));
+                        return InterpretResult::RuntimeError;
+                    }
+                },
+                OpCode::GetUpvalue => {
+                    let slot = self.read_byte() as usize;
+                    let value = unsafe {
+                        let upvalue = (&(*self.current_closure()).upvalues)[slot];
+                        *(*upvalue).location
+                    };
+                    self.push(value);
+                },
+                OpCode::SetUpvalue => {
+                    let slot = self.read_byte() as usize;
+                    let value = self.peek(0);
+                    unsafe {
+                        let upvalue = (&(*self.current_closure()).upvalues)[slot];
+                        *(*upvalue).location = value;
+                    }
+                },
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let receiver = self.peek(0);
+                    if !receiver.is_obj_type(ObjType::Instance) {
+                        self.runtime_error("Only instances have properties.");
+                        return InterpretResult::RuntimeError;
+                    }
+
+                    let instance = receiver.as_obj() as *mut ObjInstance;
+                    if let Some(value) = unsafe { (*instance).fields.get(name) } {
+                        self.pop();
+                        self.push(value);
+                    } else if !self.bind_method(unsafe { (*instance).class }, name) {
+                        return InterpretResult::RuntimeError;
+                    }
+                },
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let receiver = self.peek(1);
+                    if !receiver.is_obj_type(ObjType::Instance) {
+                        self.runtime_error("Only instances have fields.");
+                        return InterpretResult::RuntimeError;
+                    }
+
+                    let instance = receiver.as_obj() as *mut ObjInstance;
+                    unsafe { (*instance).fields.set(name, self.peek(0)) };
+                    let value = self.pop();
+                    self.pop();
+                    self.push(value);
+                },
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = self.pop().as_obj() as *mut ObjClass;
+                    if !self.bind_method(superclass, name) {
+                        return InterpretResult::RuntimeError;
+                    }
+                },
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::boolean(values_equal(a, b)));
+                },
+                OpCode::Greater => {
+                    let Some((a, b)) = self.binary_operands() else {return InterpretResult::RuntimeError};
+                    self.push(Value::boolean(a > b));
+                },
+                OpCode::GreaterEqual => {
+                    let Some((a, b)) = self.binary_operands() else {return InterpretResult::RuntimeError};
+                    self.push(Value::boolean(a >= b));
+                },
+                OpCode::Less => {
+                    let Some((a, b)) = self.binary_operands() else {return InterpretResult::RuntimeError};
+                    self.push(Value::boolean(a < b));
+                },
+                OpCode::LessEqual => {
+                    let Some((a, b)) = self.binary_operands() else {return InterpretResult::RuntimeError};
+                    self.push(Value::boolean(a <= b));
+                },
+                OpCode::Add => {
+                    if self.peek(0).is_string() && self.peek(1).is_string() {
+                        self.concatenate();
+                    } else if self.peek(0).is_number() && self.peek(1).is_number() {
+                        let b = self.pop().as_number();
+                        let a = self.pop().as_number();
+                        self.push(Value::number(a + b));
+                    } else {
+                        self.runtime_error("Operands must be two numbers or two strings.");
+                        return InterpretResult::RuntimeError;
+                    }
+                },
+                OpCode::Subtract => {
+                    let Some((a, b)) = self.binary_operands() else {return InterpretResult::RuntimeError};
+                    self.push(Value::number(a - b));
+                },
+                OpCode::Multiply => {
+                    let Some((a, b)) = self.binary_operands() else {return InterpretResult::RuntimeError};
+                    self.push(Value::number(a * b));
+                },
+                OpCode::Divide => {
+                    let Some((a, b)) = self.binary_operands() else {return InterpretResult::RuntimeError};
+                    self.push(Value::number(a / b));
+                },
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::boolean(value.is_falsey()));
+                },
+                OpCode::Negate => {
+                    if !self.peek(0).is_number() {
+                        self.runtime_error("Operand must be a number.");
+                        return InterpretResult::RuntimeError;
+                    }
+                    let value = self.pop().as_number();
+                    self.push(Value::number(-value));
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    writeln!(self.stdout, "{}", value).expect("could not write output");
+                },
+                OpCode::Jump => {
+                    let offset = self.read_short() as usize;
+                    self.ip = unsafe { self.ip.add(offset) };
+                },
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_short() as usize;
+                    if self.peek(0).is_falsey() {
+                        self.ip = unsafe { self.ip.add(offset) };
+                    }
+                },
+                OpCode::Loop => {
+                    let offset = self.read_short() as usize;
+                    self.ip = unsafe { self.ip.sub(offset) };
+                },
+                OpCode::Call => {
+                    let arg_count = self.read_byte() as usize;
+                    if !self.call_value(self.peek(arg_count), arg_count) {
+                        return InterpretResult::RuntimeError;
+                    }
+                },
+                OpCode::Invoke => {
+                    let method = self.read_string();
+                    let arg_count = self.read_byte() as usize;
+                    if !self.invoke(method, arg_count) {
+                        return InterpretResult::RuntimeError;
+                    }
+                },
+                OpCode::SuperInvoke => {
+                    let method = self.read_string();
+                    let arg_count = self.read_byte() as usize;
+                    let superclass = self.pop().as_obj() as *mut ObjClass;
+                    if !self.invoke_from_class(superclass, method, arg_count) {
+                        return InterpretResult::RuntimeError;
+                    }
+                },
+                OpCode::Closure => {
+                    let function = self.read_constant().as_obj() as *mut ObjFunction;
+                    // the function is one of the current chunk's constants, so it's already rooted
+                    let closure = unsafe { self.new_closure(function) };
+                    self.push(Value::obj(closure as *mut Obj));
+
+                    let upvalue_count = unsafe { (*function).upvalue_count };
+                    for i in 0..upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let upvalue = if is_local {
+                            self.capture_upvalue(unsafe { self.slots.add(index) })
+                        } else {
+                            unsafe { (&(*self.current_closure()).upvalues)[index] }
+                        };
+                        unsafe { (&mut (*closure).upvalues)[i] = upvalue };
+                    }
+                },
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(unsafe { self.stack_top.sub(1) });
+                    self.pop();
+                },
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = self.new_class(name);
+                    self.push(Value::obj(class as *mut Obj));
+                },
+                OpCode::Inherit => {
+                    let superclass = self.peek(1);
+                    if !superclass.is_obj_type(ObjType::Class) {
+                        self.runtime_error("Superclass must be a class.");
+                        return InterpretResult::RuntimeError;
+                    }
+
+                    // copy-down: methods are copied before the subclass's own ones override them
+                    let subclass = self.peek(0).as_obj() as *mut ObjClass;
+                    unsafe {
+                        let superclass = superclass.as_obj() as *mut ObjClass;
+                        (*superclass).methods.add_all(&mut (*subclass).methods);
+                    }
+                    self.pop();
+                },
+                OpCode::Method => {
+                    let name = self.read_string();
+                    self.define_method(name);
+                },
+                OpCode::Return => {
+                    let result = self.pop();
+                    self.close_upvalues(self.slots);
+                    self.frames.pop();
+                    if self.frames.is_empty() {
+                        self.pop();
+                        return InterpretResult::Ok;
+                    }
+
+                    self.stack_top = self.slots;
+                    self.push(result);
+                    self.load_frame();
+                },
+            }
+        }
+    }
+}
+
+impl Drop for VM {
+    fn drop(&mut self) {
+        self.free_objects();
+    }
+}
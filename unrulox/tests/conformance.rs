@@ -0,0 +1,13 @@
+#[path = "../../testing/conformance.rs"]
+mod conformance;
+
+#[test]
+fn corpus() {
+    conformance::run_corpus(env!("CARGO_BIN_EXE_unrulox"), &[]);
+}
+
+/// the same corpus again, collecting before every allocation so an unrooted object shows up as a wrong answer
+#[test]
+fn corpus_with_stress_gc() {
+    conformance::run_corpus(env!("CARGO_BIN_EXE_unrulox"), &["--stress-gc"]);
+}
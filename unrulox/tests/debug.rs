@@ -0,0 +1,17 @@
+use unrulox::chunk::{Chunk, OpCode};
+use unrulox::debug::{disassemble_chunk, disassemble_instruction};
+use unrulox::value::Value;
+
+/// chunks don't have to come from the compiler, a closure over something that isn't a function is printed
+/// as such rather than read as one
+#[test]
+fn closure_over_a_number_is_a_bad_operand() {
+    let mut chunk = Chunk::new();
+    let constant = chunk.add_constant(Value::number(1.0));
+    chunk.write_op(OpCode::Closure, 1);
+    chunk.write(constant as u8, 1);
+    chunk.write_op(OpCode::Return, 1);
+
+    assert_eq!(disassemble_instruction(&chunk, 0), 2);
+    disassemble_chunk(&chunk, "by hand");
+}
@@ -0,0 +1,24 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// stderr and the exit code of running the program through the binary
+fn run(name: &str, source: &str) -> (String, Option<i32>) {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_unrulox")).arg(&path).output().unwrap();
+    (String::from_utf8(output.stderr).unwrap(), output.status.code())
+}
+
+/// frames run out at 64, but locals plus the operands waiting on each call fill the value stack first
+#[test]
+fn a_full_value_stack_is_a_runtime_error() {
+    let locals: String = (0..250).map(|i| format!("  var a{} = {};\n", i, i)).collect();
+    let call = format!("{}f(n - 1){}", "1 + (".repeat(20), ")".repeat(20));
+    let source = format!("fun f(n) {{\n{}  if (n > 0) return {};\n  return 0;\n}}\nprint f(60);\n", locals, call);
+
+    let (stderr, code) = run("full_value_stack.lox", &source);
+    assert_eq!(code, Some(70), "{}", stderr);
+    assert!(stderr.starts_with("Stack overflow.\n"), "{}", stderr);
+    assert!(stderr.ends_with("[line 252] in f()\n[line 255] in script\n"), "{}", stderr);
+}